@@ -0,0 +1,58 @@
+// rgb ctrl
+// Copyright (C) 2025 mari
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 2 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+// Band magnitudes from cava's ascii FIFO output. A matching cava config:
+//
+//   [output]
+//   method = raw
+//   raw_target = /tmp/cava.fifo
+//   data_format = ascii
+//   ascii_max_range = 1000
+//   bar_delimiter = 59   # ';'
+//
+// cava does the capture and FFT, so any PulseAudio/PipeWire monitor it can
+// read works here too.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+// One frame of `;`-separated bar heights, scaled to 0.0..=1.0.
+fn parse_frame(line: &str, max: f32) -> Vec<f32> {
+    line.split(';')
+        .filter_map(|v| v.trim().parse::<f32>().ok())
+        .map(|v| (v / max).clamp(0.0, 1.0))
+        .collect()
+}
+
+// Feeds every frame to `on_frame` until it returns false or the writer goes
+// away. Opening a FIFO blocks until cava starts writing.
+pub fn stream_bands(
+    path: &str,
+    max: f32,
+    mut on_frame: impl FnMut(Vec<f32>) -> bool,
+) -> io::Result<()> {
+    let reader = BufReader::new(File::open(path)?);
+    for line in reader.lines() {
+        let bands = parse_frame(&line?, max);
+        if !bands.is_empty() && !on_frame(bands) {
+            return Ok(());
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "audio source closed",
+    ))
+}
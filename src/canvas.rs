@@ -0,0 +1,102 @@
+// rgb ctrl
+// Copyright (C) 2025 mari
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 2 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+// One scene laid out across the whole desk. Coordinates are keyboard grid
+// cells: the keyboard covers (0, 0) to (grid_width, grid_height) and the
+// other devices are placed around it in config, so a wave leaving the
+// keyboard carries on into the mouse and RAM.
+
+use crate::config::DevicePlacement;
+use crate::effect::Water;
+use openrgb2::Color;
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Deserialize)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl Rect {
+    // The i-th of n equal columns, e.g. one per RAM stick.
+    pub fn column(&self, i: usize, n: usize) -> Rect {
+        let w = self.w / n.max(1) as f32;
+        Rect {
+            x: self.x + w * i as f32,
+            w,
+            ..*self
+        }
+    }
+
+    // Where LED `i` of `count` sits, spread along the longer side and
+    // centered on the other.
+    pub fn led(&self, i: usize, count: usize) -> (f32, f32) {
+        let t = (i as f32 + 0.5) / count.max(1) as f32;
+        if self.w > self.h {
+            (self.x + t * self.w, self.y + self.h / 2.0)
+        } else {
+            (self.x + self.w / 2.0, self.y + t * self.h)
+        }
+    }
+}
+
+pub struct Canvas<'a> {
+    keyboard: &'a [Color], // the rendered keyboard frame, row-major
+    width: usize,
+    height: usize,
+    water: &'a Water,
+}
+
+impl<'a> Canvas<'a> {
+    pub fn new(keyboard: &'a [Color], width: usize, height: usize, water: &'a Water) -> Self {
+        Canvas {
+            keyboard,
+            width,
+            height,
+            water,
+        }
+    }
+
+    // Over the keyboard this is exactly what the keyboard shows; elsewhere
+    // it is the water, which lines up with the keyboard's own.
+    pub fn sample(&self, x: f32, y: f32) -> Color {
+        if x >= 0.0 && y >= 0.0 {
+            let (cx, cy) = (x as usize, y as usize);
+            if cx < self.width && cy < self.height {
+                return self.keyboard[cy * self.width + cx];
+            }
+        }
+        self.water.color(x, y)
+    }
+
+    // Appends one color per LED of a device placed at `rect`.
+    pub fn sample_rect(&self, rect: &Rect, count: usize, out: &mut Vec<Color>) {
+        out.extend((0..count).map(|i| {
+            let (x, y) = rect.led(i, count);
+            self.sample(x, y)
+        }));
+    }
+
+    // Appends one color per LED of a device laid out by `placement`.
+    pub fn sample_placed(&self, placement: &DevicePlacement, count: usize, out: &mut Vec<Color>) {
+        out.extend((0..count).map(|i| {
+            let (x, y) = placement.led(i);
+            self.sample(x, y)
+        }));
+    }
+}
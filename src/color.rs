@@ -0,0 +1,63 @@
+// rgb ctrl
+// Copyright (C) 2025 mari
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 2 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+// Color helpers that openrgb2's `Color` doesn't have.
+
+use openrgb2::Color;
+use palette::{FromColor, Hsv, Srgb};
+
+pub trait FromHsv {
+    // Hue in degrees (wrapping), saturation and value in 0.0..=1.0.
+    fn from_hsv(h: f32, s: f32, v: f32) -> Self;
+}
+
+impl FromHsv for Color {
+    fn from_hsv(h: f32, s: f32, v: f32) -> Self {
+        let hsv = Hsv::new(h, s.clamp(0.0, 1.0), v.clamp(0.0, 1.0));
+        let rgb: Srgb<u8> = Srgb::from_color(hsv).into_format();
+        Color::new(rgb.red, rgb.green, rgb.blue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primaries_and_secondaries() {
+        assert_eq!(Color::from_hsv(0.0, 1.0, 1.0), Color::new(255, 0, 0));
+        assert_eq!(Color::from_hsv(60.0, 1.0, 1.0), Color::new(255, 255, 0));
+        assert_eq!(Color::from_hsv(120.0, 1.0, 1.0), Color::new(0, 255, 0));
+        assert_eq!(Color::from_hsv(180.0, 1.0, 1.0), Color::new(0, 255, 255));
+        assert_eq!(Color::from_hsv(240.0, 1.0, 1.0), Color::new(0, 0, 255));
+        assert_eq!(Color::from_hsv(300.0, 1.0, 1.0), Color::new(255, 0, 255));
+    }
+
+    #[test]
+    fn in_between_hues_and_partial_saturation() {
+        assert_eq!(Color::from_hsv(30.0, 1.0, 1.0), Color::new(255, 128, 0));
+        assert_eq!(Color::from_hsv(300.0, 0.5, 1.0), Color::new(255, 128, 255));
+        assert_eq!(Color::from_hsv(0.0, 0.0, 0.5), Color::new(128, 128, 128));
+        assert_eq!(Color::from_hsv(200.0, 1.0, 0.0), Color::new(0, 0, 0));
+    }
+
+    #[test]
+    fn hue_wraps_around() {
+        assert_eq!(Color::from_hsv(360.0, 1.0, 1.0), Color::new(255, 0, 0));
+        assert_eq!(Color::from_hsv(-120.0, 1.0, 1.0), Color::new(0, 0, 255));
+        assert_eq!(Color::from_hsv(480.0, 1.0, 1.0), Color::new(0, 255, 0));
+    }
+}
@@ -0,0 +1,1602 @@
+// rgb ctrl
+// Copyright (C) 2025 mari
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 2 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+// Runtime settings from ~/.config/rgb-ctrl/config.toml. Every field is
+// optional; anything missing falls back to the defaults below.
+
+use crate::canvas::Rect;
+use crate::snake::{self, SnakeDifficulty, SnakeWalls};
+use crate::{AmbientEffect, Category, DeviceEffect, Mode, layout};
+use clap::{Parser, ValueEnum};
+use openrgb2::Color;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+// --- DEFAULTS ---
+pub const GRID_WIDTH: usize = 22;
+pub const GRID_HEIGHT: usize = 6;
+pub const TICK_RATE_MS: u64 = 30; // animation (simulation) step
+pub const PRESENT_RATE_MS: u64 = 30; // how often frames are written to devices
+pub const INPUT_DEVICE_PATH: &str = "/dev/input/event9";
+
+const DEFAULT_SNAKE_CHEAT: [&str; 6] = ["up", "down", "left", "right", "up", "down"];
+
+// Command-line flags. Anything given here wins over the config file.
+#[derive(Parser)]
+#[command(version, about = "Keyboard-reactive lighting for OpenRGB devices")]
+pub struct Cli {
+    /// Input device to read key events from, e.g. /dev/input/event9.
+    /// Repeat to read several devices at once.
+    #[arg(long, value_name = "PATH")]
+    pub input: Vec<String>,
+    /// Gamepad whose D-pad and stick press the arrow keys
+    #[arg(long, value_name = "PATH")]
+    pub gamepad: Option<String>,
+    /// Animation tick in milliseconds
+    #[arg(long, value_name = "MS")]
+    pub tick_ms: Option<u64>,
+    /// OpenRGB server to connect to (default 127.0.0.1:6742)
+    #[arg(long, value_name = "HOST:PORT", env = "OPENRGB_ADDR")]
+    pub server: Option<String>,
+    /// Mode to start in (ambient, snake, metronome, screen, audio, tetris, minesweeper, pomodoro, life, heatmap, rainbow, solid, breathe, matrix, text, clock, sunrise, blend)
+    #[arg(long, value_parser = parse_mode)]
+    pub mode: Option<Mode>,
+    /// Print incoming keycodes and their grid cells, then exit
+    #[arg(long)]
+    pub monitor_input: bool,
+    /// Draw the keyboard grid in the terminal (implies --dry-run)
+    #[arg(long)]
+    pub preview: bool,
+    /// Render to mock devices instead of OpenRGB
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Seed for everything random (food, pieces, ...), to replay a run
+    #[arg(long, value_name = "U64")]
+    pub seed: Option<u64>,
+    /// Guided first-run setup that writes a starter config
+    #[arg(long)]
+    pub setup: bool,
+    /// Let the snake wrap around the board edges instead of dying
+    #[arg(long)]
+    pub snake_wrap: bool,
+    /// What to do with the LEDs on Ctrl-C
+    #[arg(long, value_enum, value_name = "off|black|leave")]
+    pub on_exit: Option<OnExit>,
+    /// Serve a small HTTP API for mode and brightness on this address
+    #[arg(long, value_name = "HOST:PORT")]
+    pub http: Option<String>,
+    /// Serve Prometheus metrics at /metrics on this address
+    #[arg(long, value_name = "HOST:PORT")]
+    pub metrics: Option<String>,
+}
+
+// Ctrl-C always exits the render loop and saves state; this only decides
+// the final LED write. `Off` makes none, like `Leave`.
+#[derive(Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OnExit {
+    Off,
+    Black, // write one all-black frame to every controller
+    Leave, // keep the last frame
+}
+
+fn parse_mode(s: &str) -> Result<Mode, String> {
+    Mode::from_name(s).ok_or_else(|| format!("unknown mode \"{}\"", s))
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub grid_width: usize,
+    pub grid_height: usize,
+    pub tick_rate_ms: u64,
+    pub present_rate_ms: u64,
+    // A device taking longer than this to accept a frame is no longer waited
+    // on; its write carries on, and it is skipped until that is done.
+    pub device_timeout_ms: u64,
+    // Output curve applied to every channel just before sending; 1.0 leaves
+    // colors unchanged, higher values darken the mid-range.
+    pub gamma: f32,
+    // Every listed device feeds the same state, e.g. both halves of a split
+    // keyboard plus a macropad. A single string is accepted too. Unset picks
+    // the first device named like a keyboard.
+    #[serde(alias = "input_device", deserialize_with = "de_paths")]
+    pub input_devices: Vec<String>,
+    // A gamepad whose D-pad and left stick act as the arrow keys, e.g.
+    // /dev/input/by-id/usb-8BitDo_...-event-joystick.
+    pub gamepad: Option<String>,
+    pub server: Option<String>, // "host:port", unset = local OpenRGB
+    // Unix socket taking line commands (`mode snake`, `brightness 0.5`,
+    // `off`, `snapshot`) from scripts. Unset doesn't listen.
+    pub control_socket: Option<PathBuf>,
+    // Address for the HTTP API (`GET /state`, `GET /keys`, `POST /mode/snake`,
+    // `POST /brightness/0.5`). Unset doesn't listen.
+    pub http: Option<String>,
+    // Address serving Prometheus metrics at `/metrics`. Unset doesn't listen.
+    pub metrics: Option<String>,
+    #[serde(deserialize_with = "de_mode")]
+    pub start_mode: Mode,
+    pub seed: Option<u64>, // unset = a fresh seed every run
+    pub on_exit: OnExit,
+
+    // Controllers OpenRGB reports twice (same name and LED count) are
+    // collapsed into one, except those whose name contains one of these.
+    pub keep_duplicate_controllers: Vec<String>,
+    // Ignore controllers exposing no LEDs.
+    pub skip_empty_controllers: bool,
+    // Controllers that buffer colors until the mode is re-applied; matched by
+    // name substring, they get a commit after every frame.
+    pub commit_devices: Vec<String>,
+    // When no mouse is found, the first fan/misc controller whose name
+    // contains this takes over the mouse accent color instead.
+    pub mouse_fallback: Option<String>,
+    // Forced categories, checked before the device type and name heuristics.
+    pub device_overrides: Vec<DeviceOverride>,
+    // What each category (or a device by name) shows.
+    pub device_effects: DeviceEffectsConfig,
+    // How often each category is written, in frames.
+    pub update_every: UpdateEveryConfig,
+
+    // Modes listed in `idle_timeouts` fall back to `home_mode` after that many
+    // idle seconds.
+    #[serde(deserialize_with = "de_mode")]
+    pub home_mode: Mode,
+    pub idle_timeouts: HashMap<String, u64>,
+    // Cross-fade between the old and new mode on every switch; 0 cuts.
+    pub transition_secs: f32,
+    pub playlist: PlaylistConfig,
+    // Everything ramps up from dark over this long at startup; 0 starts lit.
+    pub fade_in_secs: f32,
+
+    pub water: WaterParams,
+    pub day_night: DayNightConfig,
+    pub metronome: MetronomeConfig,
+    pub snake: SnakeConfig,
+    pub game_over: GameOverConfig,
+    pub highlight: HighlightConfig,
+    pub lock_keys: LockKeysConfig,
+    pub ripple: RippleConfig,
+    pub key_glow: KeyGlowConfig,
+    pub bloom: BloomConfig,
+    pub blend: BlendConfig,
+    pub rainbow: RainbowConfig,
+    pub solid: SolidConfig,
+    pub breathe: BreatheConfig,
+    pub text: TextConfig,
+    pub clock: ClockConfig,
+    pub pomodoro: PomodoroConfig,
+    pub matrix: MatrixConfig,
+    pub screen: ScreenConfig,
+    pub heatmap: HeatmapConfig,
+    pub audio: AudioConfig,
+    pub system_tint: SystemTintConfig,
+    pub mpris: MprisConfig,
+    pub ram: RamConfig,
+    pub mouse: MouseConfig,
+    pub strip: StripConfig,
+    pub canvas: CanvasConfig,
+    pub brightness: BrightnessConfig,
+    pub idle_dim: IdleDimConfig,
+    pub sunrise: SunriseConfig,
+}
+
+// [[device_overrides]]
+// name = "Gigabyte RGB Fusion"   # or: contains = "fusion"
+// category = "fans"
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceOverride {
+    pub name: Option<String>,     // exact, case-insensitive
+    pub contains: Option<String>, // substring, case-insensitive
+    pub category: Category,
+}
+
+impl DeviceOverride {
+    pub fn matches(&self, device: &str) -> bool {
+        name_matches(&self.name, &self.contains, device)
+    }
+}
+
+fn name_matches(name: &Option<String>, contains: &Option<String>, device: &str) -> bool {
+    let device = device.to_lowercase();
+    name.as_ref().is_some_and(|n| n.to_lowercase() == device)
+        || contains
+            .as_ref()
+            .is_some_and(|n| device.contains(&n.to_lowercase()))
+}
+
+// [device_effects]
+// mice = "rainbow"
+// fans = "gradient"   # `strip` flowing along the LED chain
+// rams = "scene"      # the RAM's spot on the canvas
+//
+// [[device_effects.devices]]
+// contains = "trident"
+// effect = "off"
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct DeviceEffectsConfig {
+    pub keyboards: DeviceEffect,
+    pub mice: DeviceEffect,
+    pub rams: DeviceEffect,
+    pub fans: DeviceEffect,
+    pub devices: Vec<DeviceEffectOverride>, // checked first, in order
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceEffectOverride {
+    pub name: Option<String>,     // exact, case-insensitive
+    pub contains: Option<String>, // substring, case-insensitive
+    pub effect: DeviceEffect,
+}
+
+impl DeviceEffectsConfig {
+    pub fn get(&self, category: Category, device: &str) -> DeviceEffect {
+        if let Some(o) = self
+            .devices
+            .iter()
+            .find(|o| name_matches(&o.name, &o.contains, device))
+        {
+            return o.effect;
+        }
+        match category {
+            Category::Keyboard => self.keyboards,
+            Category::Mouse => self.mice,
+            Category::Ram => self.rams,
+            Category::Fan => self.fans,
+        }
+    }
+}
+
+impl Default for UpdateEveryConfig {
+    fn default() -> Self {
+        Self {
+            keyboards: 1,
+            mice: 1,
+            rams: 3,
+            fans: 1,
+        }
+    }
+}
+
+impl Default for DeviceEffectsConfig {
+    fn default() -> Self {
+        Self {
+            keyboards: DeviceEffect::Scene,
+            mice: DeviceEffect::Water,
+            rams: DeviceEffect::Ram,
+            fans: DeviceEffect::Off,
+            devices: Vec::new(),
+        }
+    }
+}
+
+// Frame divisors: 3 writes every third frame. At the default 30ms present
+// rate, `rams = 3` is about every 100ms and `fans = 33` about once a second.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct UpdateEveryConfig {
+    pub keyboards: u64,
+    pub mice: u64,
+    pub rams: u64,
+    pub fans: u64,
+}
+
+impl UpdateEveryConfig {
+    pub fn get(&self, category: Category) -> u64 {
+        match category {
+            Category::Keyboard => self.keyboards,
+            Category::Mouse => self.mice,
+            Category::Ram => self.rams,
+            Category::Fan => self.fans,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct MetronomeConfig {
+    pub bpm: f32,           // full sweeps across the grid per minute
+    pub hit_window_ms: f32, // either side of a beat
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SnakeConfig {
+    // Snake plays in a centered sub-region of the grid. Unset picks the widest
+    // board that stays within `max_aspect` (width:height), e.g. 6x6 on 22x6.
+    pub board_size: Option<(i32, i32)>,
+    pub max_aspect: f32,
+    pub outside_ambient: bool, // keep water running around the board
+    pub wrap: bool,            // leave one edge, come back on the other
+    pub two_player: bool,      // WASD and the arrows each steer a snake
+    pub difficulty: SnakeDifficulty,
+    // Obstacles inside the board: "none", "pillars", "bars" or "random",
+    // which walls off each cell with `wall_chance`.
+    pub walls: SnakeWalls,
+    pub wall_chance: f64,
+    // Chance a spawned food is a phase power-up that disables self-collision.
+    pub powerup_chance: f64,
+    pub phase_secs: u64,
+    // Key names that switch to snake, e.g. ["up", "down", "left", "right"].
+    pub cheat: Vec<String>,
+    #[serde(skip)]
+    pub cheat_codes: Vec<u16>, // parsed from `cheat` by `validate`
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct GameOverConfig {
+    // Shown this long before handing back to ambient, unless in attract mode.
+    pub secs: f32,
+    // The first second blinks between the two colors every `blink_ms`.
+    pub blink_ms: u64,
+    #[serde(deserialize_with = "de_color")]
+    pub color: Color,
+    #[serde(deserialize_with = "de_color")]
+    pub blink_color: Color,
+    // After the flash, scroll "<text> <score>" across the grid.
+    pub attract: bool,
+    pub text: String,
+    pub attract_secs: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct LockKeysConfig {
+    // Caps Lock and Num Lock glow while their lock is on, over every mode.
+    pub enabled: bool,
+    #[serde(deserialize_with = "de_color")]
+    pub color: Color,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct HighlightConfig {
+    // Persistent glow on the most recently pressed key, drawn over every mode.
+    pub enabled: bool,
+    #[serde(deserialize_with = "de_color")]
+    pub color: Color,
+    pub decay_secs: Option<f32>, // unset = stay lit until the next key
+}
+
+// The rolling background. Three waves (diagonal, across, down) are averaged
+// into a level that picks a color along `palette`, dark to light, and sets
+// its brightness to `floor + depth * level` (level runs -1.0..=1.0).
+// [water]
+// palette = ["#400000", "#ff4000", "#ffc040"]   # lava
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct WaterParams {
+    pub speed: f32,            // wave phase per tick
+    pub frequencies: [f32; 3], // per cell, diagonal / across / down
+    pub floor: f32,
+    pub depth: f32,
+    #[serde(deserialize_with = "de_colors")]
+    pub palette: Vec<Color>,
+}
+
+// Fades the water to `night` from `night_at` and back from `day_at`, local
+// "HH:MM" times, each over `fade_mins`. The waves keep the speed and
+// frequencies of `[water]`; only night's floor, depth and palette are used.
+// [day_night]
+// enabled = true
+// night = { floor = 0.1, depth = 0.3, palette = ["#601000", "#ff7030"] }
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct DayNightConfig {
+    pub enabled: bool,
+    pub day_at: String,
+    pub night_at: String,
+    pub fade_mins: f32,
+    pub night: WaterParams,
+    #[serde(skip)]
+    pub day_secs: u32, // seconds after midnight, parsed by `validate`
+    #[serde(skip)]
+    pub night_secs: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct RippleConfig {
+    // Crests blend toward this color, strongest mid-ring, so overlapping
+    // ripples settle on it instead of clipping.
+    #[serde(deserialize_with = "de_color")]
+    pub color: Color,
+    // The old look: add white to every channel, saturating.
+    pub additive: bool,
+    pub max_age: f32, // ticks a key press ring lasts
+    pub speed: f32,   // cells the ring grows per tick
+    pub width: f32,   // cells either side of the crest
+    // Above 1.0 key press rings run out along the key's row that many times
+    // faster than up and down, for a wave that follows the typing. 1.0 is
+    // round.
+    pub row_bias: f32,
+    // Presses on a cell that rang less than this long ago glow but start no
+    // new ring, so fast typing stays readable. 0 rings every press.
+    pub min_interval_ms: u64,
+    // Rings alive at once; a new one past this replaces the oldest.
+    pub max_count: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct KeyGlowConfig {
+    // Ambient mode: every pressed key lights up and fades out on its own,
+    // alongside the ripple.
+    pub enabled: bool,
+    #[serde(deserialize_with = "de_color")]
+    pub color: Color,
+    pub decay_secs: f32, // full brightness to nothing
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct BloomConfig {
+    // Cells brighter than the threshold are blurred and added back.
+    pub enabled: bool,
+    pub threshold: u8,
+    pub intensity: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct RainbowConfig {
+    pub speed: f32,  // hue cycles per second
+    pub spread: f32, // hue cycles across the grid width
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct TextConfig {
+    // Scrolled by text mode until replaced with the `text` socket command.
+    pub message: String,
+    pub speed: f32, // cells per second
+    #[serde(deserialize_with = "de_color")]
+    pub color: Color,
+    #[serde(deserialize_with = "de_color")]
+    pub background: Color,
+}
+
+// Steps through `modes` every `dwell_mins`, in order or at random. Only
+// modes on the list are rotated away from, so a game started by hand is
+// left alone, and switching by hand holds the rotation for `pause_mins`.
+// [playlist]
+// modes = ["ambient", "rainbow", "clock"]
+// dwell_mins = 10.0
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct PlaylistConfig {
+    pub modes: Vec<String>, // empty = no rotation
+    pub dwell_mins: f32,
+    pub shuffle: bool,
+    pub pause_mins: f32,
+    #[serde(skip)]
+    pub mode_list: Vec<Mode>, // `modes`, parsed by `validate`
+}
+
+// Matrix mode: drops fall down each column, trailing `tail` rows of fading
+// color. `density` is the share of columns raining at once. `color` also
+// takes "green" or "amber".
+// [matrix]
+// color = "amber"
+// density = 0.5
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct MatrixConfig {
+    #[serde(deserialize_with = "de_matrix_color")]
+    pub color: Color,
+    pub density: f32,
+    pub speed: f32, // rows per second, varied per column
+    pub tail: f32,  // rows
+}
+
+// Pomodoro mode: space starts and pauses, R resets. Each phase ends with a
+// flash in the next phase's color.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct PomodoroConfig {
+    pub work_mins: f32,
+    pub break_mins: f32,
+    #[serde(deserialize_with = "de_color")]
+    pub work_color: Color,
+    #[serde(deserialize_with = "de_color")]
+    pub break_color: Color,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ClockConfig {
+    pub twelve_hour: bool,
+    #[serde(deserialize_with = "de_color")]
+    pub color: Color,
+    #[serde(deserialize_with = "de_color")]
+    pub background: Color,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SolidConfig {
+    // Solid mode starts on the first color; space steps through the rest.
+    #[serde(deserialize_with = "de_colors")]
+    pub colors: Vec<Color>,
+}
+
+// Breathe mode, and the "breathe" device effect: `color` fades out and
+// back once every `period_secs`.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct BreatheConfig {
+    #[serde(deserialize_with = "de_color")]
+    pub color: Color,
+    pub period_secs: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct BlendConfig {
+    // `mix` = 0.0 is all `a`, 1.0 all `b`.
+    pub a: AmbientEffect,
+    pub b: AmbientEffect,
+    pub mix: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ScreenConfig {
+    // Needs the `screen` feature. Region is [x, y, w, h] on the primary
+    // monitor; unset captures the whole thing.
+    pub region: Option<(u32, u32, u32, u32)>,
+    pub capture_ms: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct HeatmapConfig {
+    // Key counts halve over this many minutes, so the map shows recent typing.
+    pub half_life_mins: f32,
+    // Keep the counts in the state directory between runs.
+    pub persist: bool,
+    // Likewise for the lifetime press count of every key, reported by the
+    // `keys` command and `GET /keys`. Saved on shutdown.
+    pub persist_totals: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    // FIFO that cava writes ascii bars to (see audio.rs for its config).
+    pub source: String,
+    pub max: f32, // cava's ascii_max_range
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SystemTintConfig {
+    // Warms the ambient water from blue toward red as the machine works.
+    pub enabled: bool,
+    // A hwmon temp*_input file, e.g. /sys/class/hwmon/hwmon2/temp1_input.
+    // Unset follows CPU load from /proc/stat instead.
+    pub temp_sensor: Option<String>,
+    pub temp_min: f32, // degrees C that map to fully cool
+    pub temp_max: f32, // and fully warm
+    pub sample_ms: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct MprisConfig {
+    // Needs the `mpris` feature. Pulls the ambient water toward a color for
+    // the track playing, `strength` of the way.
+    pub enabled: bool,
+    pub strength: f32,
+    pub poll_ms: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct BrightnessConfig {
+    // Master multiplier for every LED, 0.0..=1.0, adjusted at runtime with
+    // the two keys below.
+    pub level: f32,
+    pub step: f32,
+    pub down_key: String,
+    pub up_key: String,
+    #[serde(skip)]
+    pub down_code: u16, // parsed by `validate`
+    #[serde(skip)]
+    pub up_code: u16,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct IdleDimConfig {
+    // Fades every device down to `level` (of the master brightness) after
+    // this many seconds without input. Unset never dims.
+    pub after_secs: Option<u64>,
+    pub level: f32,
+    pub fade_secs: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SunriseConfig {
+    // Local "HH:MM" at which to start the sunrise every day. Unset never does.
+    pub at: Option<String>,
+    pub duration_mins: f32, // dark red to full white
+    #[serde(skip)]
+    pub at_secs: Option<u32>, // seconds after midnight, parsed by `validate`
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct RamConfig {
+    // Every stick shows the same phase instead of running slightly behind
+    // the one before it.
+    pub sync: bool,
+    // Every other stick runs its gradient the opposite way.
+    pub mirror: bool,
+    // Per-channel [r, g, b] range the gradient swings through, 0-255. Some
+    // modules whine at low duty cycles: raise `min` until they go quiet.
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+// Zones of a multi-zone mouse showing "water": those named like one of
+// `breathe` pulse, the rest hold steady. Single-zone mice are filled evenly.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct MouseConfig {
+    pub breathe: Vec<String>, // zone name substrings, case-insensitive
+    pub breathe_secs: f32,    // one full pulse
+}
+
+// The "gradient" device effect: `colors` spread along the LED chain, wrapping
+// back to the first, and flowing toward the end of the chain.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct StripConfig {
+    #[serde(deserialize_with = "de_colors")]
+    pub colors: Vec<Color>,
+    pub speed: f32, // chain lengths per second
+}
+
+// Where devices showing the "scene" effect sit on the desk-wide canvas, in
+// keyboard cells. Several devices of a category share the rectangle side by
+// side.
+// [canvas]
+// mouse = { x = 23.0, y = 4.0, w = 1.0, h = 1.0 }
+// rams = { x = -4.0, y = 0.0, w = 2.0, h = 6.0 }
+//
+// A device listed in `devices` always shows the scene, its LEDs laid out in
+// a line from (x, y):
+// [[canvas.devices]]
+// contains = "strip"
+// x = -1.0
+// y = 7.0
+// angle = 0.0     # degrees clockwise from the keyboard's rows
+// spacing = 0.5   # cells between LEDs
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct CanvasConfig {
+    pub mouse: Rect,
+    pub rams: Rect,
+    pub fans: Rect,
+    pub devices: Vec<DevicePlacement>, // checked first, in order
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DevicePlacement {
+    pub name: Option<String>,     // exact, case-insensitive
+    pub contains: Option<String>, // substring, case-insensitive
+    pub x: f32,
+    pub y: f32,
+    #[serde(default)]
+    pub angle: f32,
+    #[serde(default = "default_spacing")]
+    pub spacing: f32,
+}
+
+fn default_spacing() -> f32 {
+    1.0
+}
+
+impl CanvasConfig {
+    pub fn placement(&self, device: &str) -> Option<&DevicePlacement> {
+        self.devices
+            .iter()
+            .find(|p| name_matches(&p.name, &p.contains, device))
+    }
+}
+
+impl DevicePlacement {
+    // Where LED `i` sits on the canvas.
+    pub fn led(&self, i: usize) -> (f32, f32) {
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+        let along = i as f32 * self.spacing;
+        (self.x + along * cos, self.y + along * sin)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            grid_width: GRID_WIDTH,
+            grid_height: GRID_HEIGHT,
+            tick_rate_ms: TICK_RATE_MS,
+            present_rate_ms: PRESENT_RATE_MS,
+            device_timeout_ms: 50,
+            gamma: 1.0,
+            input_devices: Vec::new(),
+            gamepad: None,
+            server: None,
+            control_socket: None,
+            http: None,
+            metrics: None,
+            start_mode: Mode::Ambient,
+            seed: None,
+            on_exit: OnExit::Black,
+            keep_duplicate_controllers: Vec::new(),
+            skip_empty_controllers: true,
+            commit_devices: Vec::new(),
+            mouse_fallback: None,
+            device_overrides: Vec::new(),
+            device_effects: DeviceEffectsConfig::default(),
+            update_every: UpdateEveryConfig::default(),
+            home_mode: Mode::Ambient,
+            idle_timeouts: HashMap::from([
+                ("snake".to_string(), 30),
+                ("metronome".to_string(), 120),
+            ]),
+            transition_secs: 0.4,
+            playlist: PlaylistConfig::default(),
+            fade_in_secs: 1.5,
+            water: WaterParams::default(),
+            day_night: DayNightConfig::default(),
+            metronome: MetronomeConfig::default(),
+            snake: SnakeConfig::default(),
+            game_over: GameOverConfig::default(),
+            highlight: HighlightConfig::default(),
+            lock_keys: LockKeysConfig::default(),
+            ripple: RippleConfig::default(),
+            key_glow: KeyGlowConfig::default(),
+            bloom: BloomConfig::default(),
+            blend: BlendConfig::default(),
+            rainbow: RainbowConfig::default(),
+            solid: SolidConfig::default(),
+            breathe: BreatheConfig::default(),
+            text: TextConfig::default(),
+            clock: ClockConfig::default(),
+            pomodoro: PomodoroConfig::default(),
+            matrix: MatrixConfig::default(),
+            screen: ScreenConfig::default(),
+            heatmap: HeatmapConfig::default(),
+            audio: AudioConfig::default(),
+            system_tint: SystemTintConfig::default(),
+            mpris: MprisConfig::default(),
+            ram: RamConfig::default(),
+            mouse: MouseConfig::default(),
+            strip: StripConfig::default(),
+            canvas: CanvasConfig::default(),
+            brightness: BrightnessConfig::default(),
+            idle_dim: IdleDimConfig::default(),
+            sunrise: SunriseConfig::default(),
+        }
+    }
+}
+
+impl Default for MetronomeConfig {
+    fn default() -> Self {
+        Self {
+            bpm: 60.0,
+            hit_window_ms: 70.0,
+        }
+    }
+}
+
+impl Default for SnakeConfig {
+    fn default() -> Self {
+        Self {
+            board_size: None,
+            max_aspect: 1.0,
+            outside_ambient: false,
+            wrap: false,
+            two_player: false,
+            difficulty: SnakeDifficulty::Normal,
+            walls: SnakeWalls::None,
+            wall_chance: 0.1,
+            powerup_chance: 0.1,
+            phase_secs: 5,
+            cheat: DEFAULT_SNAKE_CHEAT.iter().map(|k| k.to_string()).collect(),
+            cheat_codes: DEFAULT_SNAKE_CHEAT
+                .iter()
+                .filter_map(|k| layout::key_code(k))
+                .collect(),
+        }
+    }
+}
+
+impl Default for GameOverConfig {
+    fn default() -> Self {
+        Self {
+            secs: 5.0,
+            blink_ms: 250,
+            color: Color::new(255, 0, 0),
+            blink_color: Color::new(0, 0, 0),
+            attract: false,
+            text: "OVER".to_string(),
+            attract_secs: 8.0,
+        }
+    }
+}
+
+impl Default for LockKeysConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            color: Color::new(255, 40, 0),
+        }
+    }
+}
+
+impl Default for HighlightConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            color: Color::new(255, 160, 0),
+            decay_secs: None,
+        }
+    }
+}
+
+impl Default for WaterParams {
+    fn default() -> Self {
+        Self {
+            speed: 0.15,
+            frequencies: [0.4, 0.6, 0.5],
+            floor: 0.2,
+            depth: 0.5,
+            palette: vec![Color::new(200, 220, 255)], // bluish snow
+        }
+    }
+}
+
+impl Default for DayNightConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            day_at: "07:00".to_string(),
+            night_at: "21:00".to_string(),
+            fade_mins: 30.0,
+            night: WaterParams {
+                floor: 0.1,
+                depth: 0.3,
+                palette: vec![Color::new(120, 30, 0), Color::new(255, 140, 60)],
+                ..WaterParams::default()
+            },
+            day_secs: 7 * 3600,
+            night_secs: 21 * 3600,
+        }
+    }
+}
+
+impl Default for RippleConfig {
+    fn default() -> Self {
+        Self {
+            color: Color::new(210, 235, 255),
+            additive: false,
+            max_age: 12.0,
+            speed: 1.2,
+            width: 1.5,
+            row_bias: 1.0,
+            min_interval_ms: 80,
+            max_count: 32,
+        }
+    }
+}
+
+impl Default for KeyGlowConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            color: Color::new(120, 200, 255),
+            decay_secs: 0.8,
+        }
+    }
+}
+
+impl Default for BloomConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: 180,
+            intensity: 0.6,
+        }
+    }
+}
+
+impl Default for RainbowConfig {
+    fn default() -> Self {
+        Self {
+            speed: 0.1,
+            spread: 1.0,
+        }
+    }
+}
+
+impl Default for BreatheConfig {
+    fn default() -> Self {
+        Self {
+            color: Color::new(0, 120, 255),
+            period_secs: 4.0,
+        }
+    }
+}
+
+impl Default for SolidConfig {
+    fn default() -> Self {
+        Self {
+            colors: vec![
+                Color::new(255, 255, 255),
+                Color::new(255, 120, 20),
+                Color::new(0, 80, 255),
+            ],
+        }
+    }
+}
+
+impl Default for TextConfig {
+    fn default() -> Self {
+        Self {
+            message: "HELLO".to_string(),
+            speed: 8.0,
+            color: Color::new(255, 255, 255),
+            background: Color::new(0, 0, 10),
+        }
+    }
+}
+
+impl Default for PlaylistConfig {
+    fn default() -> Self {
+        Self {
+            modes: Vec::new(),
+            dwell_mins: 10.0,
+            shuffle: false,
+            pause_mins: 5.0,
+            mode_list: Vec::new(),
+        }
+    }
+}
+
+impl Default for MatrixConfig {
+    fn default() -> Self {
+        Self {
+            color: MatrixConfig::GREEN,
+            density: 0.5,
+            speed: 6.0,
+            tail: 4.0,
+        }
+    }
+}
+
+impl Default for PomodoroConfig {
+    fn default() -> Self {
+        Self {
+            work_mins: 25.0,
+            break_mins: 5.0,
+            work_color: Color::new(255, 60, 0),
+            break_color: Color::new(0, 200, 80),
+        }
+    }
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            twelve_hour: false,
+            color: Color::new(0, 180, 255),
+            background: Color::new(0, 0, 10),
+        }
+    }
+}
+
+impl Default for BlendConfig {
+    fn default() -> Self {
+        Self {
+            a: AmbientEffect::Water,
+            b: AmbientEffect::Rainbow,
+            mix: 0.2,
+        }
+    }
+}
+
+impl Default for HeatmapConfig {
+    fn default() -> Self {
+        Self {
+            half_life_mins: 30.0,
+            persist: false,
+            persist_totals: false,
+        }
+    }
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            source: "/tmp/cava.fifo".to_string(),
+            max: 1000.0,
+        }
+    }
+}
+
+impl Default for MprisConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            strength: 0.4,
+            poll_ms: 2000,
+        }
+    }
+}
+
+impl Default for SystemTintConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            temp_sensor: None,
+            temp_min: 40.0,
+            temp_max: 90.0,
+            sample_ms: 1000,
+        }
+    }
+}
+
+impl Default for RamConfig {
+    fn default() -> Self {
+        Self {
+            sync: false,
+            mirror: false,
+            min: [40.0, 55.0, 60.0],
+            max: [110.0, 180.0, 200.0], // R kept low to prevent a pink tint
+        }
+    }
+}
+
+impl Default for MouseConfig {
+    fn default() -> Self {
+        Self {
+            breathe: vec!["logo".to_string()],
+            breathe_secs: 4.0,
+        }
+    }
+}
+
+impl Default for StripConfig {
+    fn default() -> Self {
+        Self {
+            colors: vec![Color::new(0, 80, 255), Color::new(160, 0, 255)],
+            speed: 0.2,
+        }
+    }
+}
+
+impl Default for CanvasConfig {
+    fn default() -> Self {
+        Self {
+            mouse: Rect {
+                x: 23.0,
+                y: 4.0,
+                w: 1.0,
+                h: 1.0,
+            },
+            rams: Rect {
+                x: -4.0,
+                y: 0.0,
+                w: 2.0,
+                h: 6.0,
+            },
+            fans: Rect {
+                x: -4.0,
+                y: -3.0,
+                w: 30.0,
+                h: 2.0,
+            },
+            devices: Vec::new(),
+        }
+    }
+}
+
+impl Default for BrightnessConfig {
+    fn default() -> Self {
+        Self {
+            level: 1.0,
+            step: 0.1,
+            down_key: "Scroll Lock".to_string(),
+            up_key: "Pause/Break".to_string(),
+            down_code: 0,
+            up_code: 0,
+        }
+    }
+}
+
+impl Default for IdleDimConfig {
+    fn default() -> Self {
+        Self {
+            after_secs: None,
+            level: 0.15,
+            fade_secs: 3.0,
+        }
+    }
+}
+
+impl Default for SunriseConfig {
+    fn default() -> Self {
+        Self {
+            at: None,
+            duration_mins: 10.0,
+            at_secs: None,
+        }
+    }
+}
+
+impl Default for ScreenConfig {
+    fn default() -> Self {
+        Self {
+            region: None,
+            capture_ms: 250,
+        }
+    }
+}
+
+impl Config {
+    // Reads the config file if there is one, then applies `cli` over it. A
+    // missing file is not an error; a malformed one is, so typos don't
+    // silently fall back to defaults. Validated once, with everything set.
+    pub fn load(cli: &Cli) -> Result<Self, String> {
+        let (mut config, path) = Self::read()?;
+        config.apply_cli(cli);
+        config.validate().map_err(|e| match &path {
+            Some(path) => format!("Invalid config {}: {}", path.display(), e),
+            None => e,
+        })?;
+        Ok(config)
+    }
+
+    // The config file parsed, and where it was found.
+    fn read() -> Result<(Self, Option<PathBuf>), String> {
+        let Some(path) = config_path() else {
+            return Ok((Self::default(), None));
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok((Self::default(), None));
+            }
+            Err(e) => return Err(format!("Could not read {}: {}", path.display(), e)),
+        };
+        let config: Self = toml::from_str(&text)
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+        log::info!("Loaded config from {}", path.display());
+        Ok((config, Some(path)))
+    }
+
+    // Length of one simulation step, the `dt` effects are advanced by.
+    pub fn tick_secs(&self) -> f32 {
+        self.tick_rate_ms as f32 / 1000.0
+    }
+
+    fn apply_cli(&mut self, cli: &Cli) {
+        if !cli.input.is_empty() {
+            self.input_devices = cli.input.clone();
+        }
+        if let Some(gamepad) = &cli.gamepad {
+            self.gamepad = Some(gamepad.clone());
+        }
+        if let Some(server) = &cli.server {
+            self.server = Some(server.clone());
+        }
+        if let Some(http) = &cli.http {
+            self.http = Some(http.clone());
+        }
+        if let Some(metrics) = &cli.metrics {
+            self.metrics = Some(metrics.clone());
+        }
+        if let Some(tick_ms) = cli.tick_ms {
+            self.tick_rate_ms = tick_ms;
+        }
+        if let Some(seed) = cli.seed {
+            self.seed = Some(seed);
+        }
+        if let Some(mode) = cli.mode {
+            self.start_mode = mode;
+        }
+        if cli.snake_wrap {
+            self.snake.wrap = true;
+        }
+        if let Some(on_exit) = cli.on_exit {
+            self.on_exit = on_exit;
+        }
+    }
+
+    fn validate(&mut self) -> Result<(), String> {
+        if self.grid_width == 0 || self.grid_height == 0 {
+            return Err("grid_width and grid_height must be positive".to_string());
+        }
+        if self.tick_rate_ms == 0 || self.present_rate_ms == 0 || self.device_timeout_ms == 0 {
+            return Err(
+                "tick_rate_ms, present_rate_ms and device_timeout_ms must be positive".to_string(),
+            );
+        }
+        if !self.gamma.is_finite() || self.gamma <= 0.0 {
+            return Err("gamma must be positive".to_string());
+        }
+        if self.water.palette.is_empty() {
+            return Err("water.palette must not be empty".to_string());
+        }
+        let ripple = &self.ripple;
+        if [ripple.max_age, ripple.speed, ripple.width, ripple.row_bias]
+            .iter()
+            .any(|v| !v.is_finite() || *v <= 0.0)
+        {
+            return Err("ripple.max_age, speed, width and row_bias must be positive".to_string());
+        }
+        if ripple.max_count == 0 {
+            return Err("ripple.max_count must be at least 1".to_string());
+        }
+        let game_over = &self.game_over;
+        if game_over.secs <= 0.0 || game_over.blink_ms == 0 || game_over.attract_secs <= 0.0 {
+            return Err("game_over.secs, blink_ms and attract_secs must be positive".to_string());
+        }
+        if self.metronome.bpm <= 0.0 || self.metronome.hit_window_ms < 0.0 {
+            return Err(
+                "metronome.bpm must be positive and hit_window_ms not negative".to_string(),
+            );
+        }
+        if self.screen.capture_ms == 0 {
+            return Err("screen.capture_ms must be positive".to_string());
+        }
+        if self.text.speed <= 0.0 || self.rainbow.speed <= 0.0 {
+            return Err("text.speed and rainbow.speed must be positive".to_string());
+        }
+        if !self.transition_secs.is_finite() || self.transition_secs < 0.0 {
+            return Err("transition_secs must not be negative".to_string());
+        }
+        if !self.fade_in_secs.is_finite() || self.fade_in_secs < 0.0 {
+            return Err("fade_in_secs must not be negative".to_string());
+        }
+        if !(0.0..0.5).contains(&self.snake.wall_chance) {
+            return Err("snake.wall_chance must be at least 0.0 and under 0.5".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.mpris.strength) || self.mpris.poll_ms == 0 {
+            return Err(
+                "mpris.strength must be between 0.0 and 1.0 and poll_ms positive".to_string(),
+            );
+        }
+        if self.system_tint.sample_ms == 0 {
+            return Err("system_tint.sample_ms must be positive".to_string());
+        }
+        if let Some(o) = self
+            .device_overrides
+            .iter()
+            .find(|o| o.name.is_none() == o.contains.is_none())
+        {
+            return Err(format!(
+                "device_overrides entry for {:?} needs exactly one of name or contains",
+                o.category
+            ));
+        }
+        let every = &self.update_every;
+        if [every.keyboards, every.mice, every.rams, every.fans].contains(&0) {
+            return Err("update_every values must be at least 1".to_string());
+        }
+        if let Some(o) = self
+            .device_effects
+            .devices
+            .iter()
+            .find(|o| o.name.is_none() == o.contains.is_none())
+        {
+            return Err(format!(
+                "device_effects entry for {:?} needs exactly one of name or contains",
+                o.effect
+            ));
+        }
+        if let Some(p) = self
+            .canvas
+            .devices
+            .iter()
+            .find(|p| p.name.is_none() == p.contains.is_none())
+        {
+            return Err(format!(
+                "canvas.devices entry at ({}, {}) needs exactly one of name or contains",
+                p.x, p.y
+            ));
+        }
+        if self
+            .canvas
+            .devices
+            .iter()
+            .any(|p| !p.spacing.is_finite() || p.spacing <= 0.0)
+        {
+            return Err("canvas.devices spacing must be positive".to_string());
+        }
+        let (grid_w, grid_h) = (self.grid_width as i32, self.grid_height as i32);
+        let (board_w, board_h) = snake::board_size(grid_w, grid_h, &self.snake);
+        if !(self.snake.max_aspect.is_finite() && self.snake.max_aspect > 0.0)
+            || board_w < snake::MIN_BOARD.0
+            || board_h < snake::MIN_BOARD.1
+        {
+            return Err(format!(
+                "snake board is {}x{}, it must be at least {}x{}",
+                board_w,
+                board_h,
+                snake::MIN_BOARD.0,
+                snake::MIN_BOARD.1
+            ));
+        }
+        let codes: Option<Vec<u16>> = self
+            .snake
+            .cheat
+            .iter()
+            .map(|k| layout::key_code(k))
+            .collect();
+        self.snake.cheat_codes = match codes {
+            Some(codes) if !codes.is_empty() => codes,
+            // Deliberately not an error: a bad cheat only loses the easter
+            // egg, not worth refusing to start over. `validate` runs once, so
+            // this is logged once.
+            _ => {
+                log::warn!(
+                    "Invalid snake.cheat {:?}, using the default sequence",
+                    self.snake.cheat
+                );
+                self.snake.cheat = SnakeConfig::default().cheat;
+                DEFAULT_SNAKE_CHEAT
+                    .iter()
+                    .filter_map(|k| layout::key_code(k))
+                    .collect()
+            }
+        };
+        if !(0.0..=1.0).contains(&self.brightness.level) {
+            return Err("brightness.level must be between 0.0 and 1.0".to_string());
+        }
+        let (min, max) = (self.ram.min, self.ram.max);
+        if (0..3).any(|i| !(0.0..=max[i]).contains(&min[i]) || max[i] > 255.0) {
+            return Err("ram.min and ram.max must satisfy 0 <= min <= max <= 255".to_string());
+        }
+        if !self.mouse.breathe_secs.is_finite() || self.mouse.breathe_secs <= 0.0 {
+            return Err("mouse.breathe_secs must be positive".to_string());
+        }
+        if self.strip.colors.is_empty() {
+            return Err("strip.colors must not be empty".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.idle_dim.level) {
+            return Err("idle_dim.level must be between 0.0 and 1.0".to_string());
+        }
+        if let Some(at) = &self.sunrise.at {
+            self.sunrise.at_secs =
+                Some(parse_hh_mm(at).ok_or_else(|| format!("sunrise.at '{}' is not HH:MM", at))?);
+        }
+        let day_night = &mut self.day_night;
+        day_night.day_secs = parse_hh_mm(&day_night.day_at)
+            .ok_or_else(|| format!("day_night.day_at '{}' is not HH:MM", day_night.day_at))?;
+        day_night.night_secs = parse_hh_mm(&day_night.night_at)
+            .ok_or_else(|| format!("day_night.night_at '{}' is not HH:MM", day_night.night_at))?;
+        if !day_night.fade_mins.is_finite() || day_night.fade_mins < 0.0 {
+            return Err("day_night.fade_mins must not be negative".to_string());
+        }
+        if day_night.night.palette.is_empty() {
+            return Err("day_night.night.palette must not be empty".to_string());
+        }
+        let pomodoro = &self.pomodoro;
+        if !(pomodoro.work_mins > 0.0 && pomodoro.break_mins > 0.0) {
+            return Err("pomodoro.work_mins and break_mins must be positive".to_string());
+        }
+        if self.sunrise.duration_mins <= 0.0 {
+            return Err("sunrise.duration_mins must be positive".to_string());
+        }
+        let key = |name: &str| {
+            layout::key_code(name).ok_or_else(|| format!("unknown brightness key '{}'", name))
+        };
+        self.brightness.down_code = key(&self.brightness.down_key)?;
+        self.brightness.up_code = key(&self.brightness.up_key)?;
+        for name in self.idle_timeouts.keys() {
+            if Mode::from_name(name).is_none() {
+                return Err(format!("unknown mode '{}' in idle_timeouts", name));
+            }
+        }
+        if self.solid.colors.is_empty() {
+            return Err("solid.colors must not be empty".to_string());
+        }
+        if let Mode::Solid(_) = self.home_mode {
+            self.home_mode = self.solid.mode();
+        }
+        if let Mode::Solid(_) = self.start_mode {
+            self.start_mode = self.solid.mode();
+        }
+        let matrix = &self.matrix;
+        if !(matrix.density > 0.0 && matrix.density <= 1.0) {
+            return Err("matrix.density must be between 0.0 and 1.0".to_string());
+        }
+        if matrix.speed <= 0.0 || matrix.tail <= 0.0 {
+            return Err("matrix.speed and tail must be positive".to_string());
+        }
+        if self.breathe.period_secs <= 0.0 {
+            return Err("breathe.period_secs must be positive".to_string());
+        }
+        if let Mode::Breathe(_) = self.home_mode {
+            self.home_mode = self.breathe.mode();
+        }
+        if let Mode::Breathe(_) = self.start_mode {
+            self.start_mode = self.breathe.mode();
+        }
+        if let Mode::Blend { .. } = self.home_mode {
+            self.home_mode = self.blend.mode();
+        }
+        if let Mode::Blend { .. } = self.start_mode {
+            self.start_mode = self.blend.mode();
+        }
+        let playlist = &self.playlist;
+        if playlist.dwell_mins <= 0.0 || playlist.pause_mins < 0.0 {
+            return Err(
+                "playlist.dwell_mins must be positive and pause_mins not negative".to_string(),
+            );
+        }
+        self.playlist.mode_list = playlist
+            .modes
+            .iter()
+            .map(|name| match Mode::from_name(name) {
+                Some(Mode::Solid(_)) => Ok(self.solid.mode()),
+                Some(Mode::Breathe(_)) => Ok(self.breathe.mode()),
+                Some(Mode::Blend { .. }) => Ok(self.blend.mode()),
+                Some(mode) => Ok(mode),
+                None => Err(format!("unknown mode '{}' in playlist", name)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+}
+
+impl SolidConfig {
+    pub fn mode(&self) -> Mode {
+        Mode::Solid(self.colors[0])
+    }
+}
+
+impl MatrixConfig {
+    pub const GREEN: Color = Color::new(0, 255, 70);
+    pub const AMBER: Color = Color::new(255, 176, 0);
+}
+
+impl BreatheConfig {
+    pub fn mode(&self) -> Mode {
+        Mode::Breathe(self.color)
+    }
+}
+
+impl PlaylistConfig {
+    pub fn dwell(&self) -> std::time::Duration {
+        std::time::Duration::from_secs_f32(self.dwell_mins * 60.0)
+    }
+}
+
+impl BlendConfig {
+    pub fn mode(&self) -> Mode {
+        Mode::Blend {
+            a: self.a,
+            b: self.b,
+            mix: self.mix,
+        }
+    }
+}
+
+impl DayNightConfig {
+    // How far toward night the water is at `now`, seconds after midnight.
+    // Whichever boundary passed last decides the direction of the fade.
+    pub fn night_mix(&self, now: u32) -> f32 {
+        let since = |at: u32| (now as i64 - at as i64).rem_euclid(86_400) as f32;
+        let fade = (self.fade_mins * 60.0).max(1.0);
+        let (since_night, since_day) = (since(self.night_secs), since(self.day_secs));
+        if since_night < since_day {
+            (since_night / fade).min(1.0)
+        } else {
+            1.0 - (since_day / fade).min(1.0)
+        }
+    }
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+    Some(base.join("rgb-ctrl").join("config.toml"))
+}
+
+// Where small bits of runtime state (high scores) are kept between runs.
+pub fn state_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/state")))?;
+    Some(base.join("rgb-ctrl"))
+}
+
+// "#RRGGBB" (the leading '#' is optional).
+pub fn parse_hex_color(s: &str) -> Option<Color> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(Color::new(channel(0)?, channel(2)?, channel(4)?))
+}
+
+fn de_color<'de, D: Deserializer<'de>>(d: D) -> Result<Color, D::Error> {
+    let s = String::deserialize(d)?;
+    parse_hex_color(&s)
+        .ok_or_else(|| serde::de::Error::custom(format!("expected \"#RRGGBB\", got \"{}\"", s)))
+}
+
+fn de_matrix_color<'de, D: Deserializer<'de>>(d: D) -> Result<Color, D::Error> {
+    let s = String::deserialize(d)?;
+    match s.to_lowercase().as_str() {
+        "green" => Ok(MatrixConfig::GREEN),
+        "amber" => Ok(MatrixConfig::AMBER),
+        _ => parse_hex_color(&s).ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "expected \"green\", \"amber\" or \"#RRGGBB\", got \"{}\"",
+                s
+            ))
+        }),
+    }
+}
+
+// "HH:MM" as seconds after midnight.
+fn parse_hh_mm(s: &str) -> Option<u32> {
+    let (h, m) = s.split_once(':')?;
+    let (h, m) = (h.trim().parse::<u32>().ok()?, m.trim().parse::<u32>().ok()?);
+    (h < 24 && m < 60).then_some(h * 3600 + m * 60)
+}
+
+fn de_colors<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Color>, D::Error> {
+    Vec::<String>::deserialize(d)?
+        .iter()
+        .map(|s| {
+            parse_hex_color(s).ok_or_else(|| {
+                serde::de::Error::custom(format!("expected \"#RRGGBB\", got \"{}\"", s))
+            })
+        })
+        .collect()
+}
+
+fn de_paths<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(d)? {
+        OneOrMany::One(path) => vec![path],
+        OneOrMany::Many(paths) => paths,
+    })
+}
+
+fn de_mode<'de, D: Deserializer<'de>>(d: D) -> Result<Mode, D::Error> {
+    let s = String::deserialize(d)?;
+    Mode::from_name(&s).ok_or_else(|| serde::de::Error::custom(format!("unknown mode \"{}\"", s)))
+}
@@ -0,0 +1,158 @@
+// rgb ctrl
+// Copyright (C) 2025 mari
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 2 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+// Line commands over a Unix socket, one reply line per command:
+//
+//   $ echo "mode snake" | socat - UNIX-CONNECT:/run/user/1000/rgb-ctrl.sock
+//   ok
+//
+// `text <message>` scrolls a message across the keys, `flash #RRGGBB [count]`
+// blinks the whole keyboard over the current mode, and `snapshot [path]`
+// writes the keyboard as it looks right now to a PNG. `keys` answers with the
+// lifetime press count of every key as JSON in place of `ok`.
+// `pomodoro start|stop|reset` drives the pomodoro timer.
+
+use crate::effect::PomodoroTimer;
+use crate::{AppState, Mode, config, snapshot};
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+pub fn set_mode(state: &mut AppState, name: &str) -> Result<(), String> {
+    let mode = state
+        .mode_by_name(name)
+        .ok_or_else(|| format!("unknown mode '{}'", name))?;
+    state.enter_mode(mode);
+    state.pause_playlist();
+    Ok(())
+}
+
+pub fn set_brightness(state: &mut AppState, level: &str) -> Result<(), String> {
+    let level = level
+        .parse::<f32>()
+        .ok()
+        .filter(|l| l.is_finite())
+        .ok_or_else(|| format!("invalid brightness '{}'", level))?;
+    state.set_brightness(level);
+    Ok(())
+}
+
+fn run_command(state: &mut AppState, line: &str) -> Result<(), String> {
+    let mut words = line.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some("mode"), Some(name), None) => set_mode(state, name),
+        (Some("brightness"), Some(level), None) => set_brightness(state, level),
+        (Some("off"), None, None) => {
+            state.set_brightness(0.0);
+            Ok(())
+        }
+        (Some("flash"), Some(color), count) => {
+            let color = config::parse_hex_color(color)
+                .ok_or_else(|| format!("invalid color '{}'", color))?;
+            let count = match count.map(str::parse::<u32>) {
+                None => 3,
+                Some(Ok(n)) if n > 0 => n,
+                Some(_) => return Err("flash count must be a positive number".to_string()),
+            };
+            state.flash(color, count);
+            Ok(())
+        }
+        (Some("text"), Some(_), _) => {
+            let message = line.trim_start()["text".len()..].trim();
+            state.text = message.to_string();
+            state.enter_mode(Mode::Text);
+            state.pause_playlist();
+            Ok(())
+        }
+        (Some("pomodoro"), Some(action), None) => {
+            match action {
+                "start" => {
+                    state.pomodoro.modify(|p| p.running = true);
+                    if state.mode != Mode::Pomodoro {
+                        state.enter_mode(Mode::Pomodoro);
+                        state.pause_playlist();
+                    }
+                }
+                "stop" => state.pomodoro.modify(|p| p.running = false),
+                "reset" => state.pomodoro.set(PomodoroTimer::new(&state.config)),
+                _ => return Err(format!("unknown pomodoro action '{}'", action)),
+            }
+            Ok(())
+        }
+        _ => Err(format!("unknown command '{}'", line.trim())),
+    }
+}
+
+// Only the grid is drawn under the lock; the PNG is encoded and written off
+// the async threads once it is released.
+async fn save_snapshot(state: &Mutex<AppState>, path: Option<&str>) -> Result<(), String> {
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => snapshot::default_path().ok_or("no state directory for snapshots")?,
+    };
+    let shot = snapshot::capture(&state.lock().unwrap());
+    let saved = tokio::task::spawn_blocking(move || shot.save(&path).map(|_| path));
+    let path = saved.await.map_err(|e| e.to_string())??;
+    info!("Saved snapshot to {}", path.display());
+    Ok(())
+}
+
+fn reply(done: Result<(), String>) -> String {
+    match done {
+        Ok(()) => "ok\n".to_string(),
+        Err(e) => format!("error: {}\n", e),
+    }
+}
+
+async fn serve_client(stream: UnixStream, state: Arc<Mutex<AppState>>) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let reply = match (words.next(), words.next(), words.next()) {
+            (Some("snapshot"), path, None) => reply(save_snapshot(&state, path).await),
+            (Some("keys"), None, None) => {
+                format!("{}\n", state.lock().unwrap().key_totals_json())
+            }
+            _ => reply(run_command(&mut state.lock().unwrap(), &line)),
+        };
+        writer.write_all(reply.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+pub async fn serve(path: &Path, state: Arc<Mutex<AppState>>) -> std::io::Result<()> {
+    // A socket left behind by a previous run would make bind fail.
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    info!("Listening for commands on {}", path.display());
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_client(stream, state).await {
+                warn!("Control client error: {}", e);
+            }
+        });
+    }
+}
@@ -0,0 +1,127 @@
+// rgb ctrl
+// Copyright (C) 2025 mari
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 2 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+// The controller operations the render loop needs, so it can drive either
+// OpenRGB or `MockController`s (`--dry-run` and tests).
+
+use openrgb2::{Color, Controller, OpenRgbResult};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Written from spawned tasks, hence the `Send` bounds.
+pub trait Device: Send + Sync + 'static {
+    // Stable while connected; used to key per-device state.
+    fn id(&self) -> usize;
+    fn name(&self) -> &str;
+    fn num_leds(&self) -> usize;
+    fn init(&self) -> impl Future<Output = OpenRgbResult<()>>;
+    fn set_leds(&self, leds: &[Color]) -> impl Future<Output = OpenRgbResult<()>> + Send;
+
+    // Latches colors written with `set_leds`, for devices that buffer them.
+    fn commit(&self) -> impl Future<Output = OpenRgbResult<()>> + Send {
+        async { Ok(()) }
+    }
+}
+
+impl Device for Controller {
+    fn id(&self) -> usize {
+        Controller::id(self)
+    }
+
+    fn name(&self) -> &str {
+        Controller::name(self)
+    }
+
+    fn num_leds(&self) -> usize {
+        Controller::num_leds(self)
+    }
+
+    async fn init(&self) -> OpenRgbResult<()> {
+        Controller::init(self).await
+    }
+
+    async fn set_leds(&self, leds: &[Color]) -> OpenRgbResult<()> {
+        Controller::set_leds(self, leds.iter().copied()).await
+    }
+
+    // Re-applying the direct mode latches the buffered colors.
+    async fn commit(&self) -> OpenRgbResult<()> {
+        self.set_controllable_mode().await
+    }
+}
+
+// Frames kept per mock; older ones are dropped so a long dry run stays small.
+const MOCK_HISTORY: usize = 1024;
+
+// Accepts every frame and remembers it.
+pub struct MockController {
+    id: usize,
+    name: String,
+    num_leds: usize,
+    frames: Mutex<VecDeque<Vec<Color>>>,
+    received: AtomicUsize, // including dropped ones
+}
+
+impl MockController {
+    pub fn new(id: usize, name: &str, num_leds: usize) -> Self {
+        MockController {
+            id,
+            name: name.to_string(),
+            num_leds,
+            frames: Mutex::new(VecDeque::new()),
+            received: AtomicUsize::new(0),
+        }
+    }
+
+    // The most recent frames received, oldest first.
+    #[cfg(test)]
+    pub fn frames(&self) -> Vec<Vec<Color>> {
+        self.frames.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn frames_received(&self) -> usize {
+        self.received.load(Ordering::Relaxed)
+    }
+}
+
+impl Device for MockController {
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn num_leds(&self) -> usize {
+        self.num_leds
+    }
+
+    async fn init(&self) -> OpenRgbResult<()> {
+        Ok(())
+    }
+
+    async fn set_leds(&self, leds: &[Color]) -> OpenRgbResult<()> {
+        let mut frames = self.frames.lock().unwrap();
+        if frames.len() == MOCK_HISTORY {
+            frames.pop_front();
+        }
+        frames.push_back(leds.to_vec());
+        self.received.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
@@ -0,0 +1,1400 @@
+// rgb ctrl
+// Copyright (C) 2025 mari
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 2 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+// Keyboard effects that own their state. `AppState` holds the active one and
+// swaps it on mode changes. The games live in their own modules.
+
+use crate::color::FromHsv;
+use crate::config::{Config, WaterParams};
+use crate::mpris::NowPlaying;
+use crate::sysmon::SystemLoad;
+use crate::{AmbientEffect, KEY_R, KEY_SPACE, Mode, font, lerp_color, scale_color};
+use chrono::Timelike;
+use log::info;
+use openrgb2::Color;
+use rand::Rng;
+use rand::rngs::StdRng;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub trait Effect: Send {
+    // Advances the effect by `dt` seconds.
+    fn update(&mut self, dt: f32);
+    fn color_at(&self, x: i32, y: i32) -> Color;
+
+    // A key went down or came back up over grid cell (x, y).
+    fn key_down(&mut self, _code: u16, _x: i32, _y: i32) {}
+    fn key_up(&mut self, _code: u16, _x: i32, _y: i32) {}
+
+    // Where to go once the effect has run its course, e.g. a lost game.
+    fn next_mode(&self) -> Option<Mode> {
+        None
+    }
+
+    // Final score, for effects that are games.
+    fn score(&self) -> Option<usize> {
+        None
+    }
+
+    // Whether Esc goes back to ambient. Effects that are ambient already, or
+    // where a stray Esc would throw a game away, say no.
+    fn exits_on_esc(&self) -> bool {
+        true
+    }
+
+    // True while a game waits on the player, which holds off idle timeouts.
+    fn paused(&self) -> bool {
+        false
+    }
+
+    // The color of whoever won, for games with more than one player.
+    fn winner(&self) -> Option<Color> {
+        None
+    }
+
+    // Called once as `AppState` switches to another mode.
+    fn leave(&mut self) {}
+}
+
+// --- WATER ---
+// The rolling background most modes fall back to. Cloning copies the clock,
+// so a new effect picks the waves up where the last one left them.
+#[derive(Clone)]
+pub struct Water {
+    pub time_tick: f32,
+    load: SystemLoad,
+    params: Arc<WaterParams>,
+    night: Option<Arc<WaterParams>>, // with `day_night` enabled
+    // How far toward `night`, 0.0..=1.0 as f32 bits. Shared by every clone,
+    // so effects follow AppState's clock.
+    night_mix: Arc<AtomicU32>,
+    now_playing: NowPlaying,
+    now_playing_strength: f32,
+    table: WaveTable,
+}
+
+// The three waves at every grid cell for one `time_tick`. Each wave only
+// depends on x, y or x + y, so filling this takes one trig call per column,
+// row and diagonal instead of three per cell.
+#[derive(Clone)]
+struct WaveTable {
+    tick: f32,
+    diagonals: Vec<f32>, // wave1, by x + y
+    columns: Vec<f32>,   // wave2, by x
+    rows: Vec<f32>,      // wave3, by y
+}
+
+impl WaveTable {
+    fn new(width: usize, height: usize, frequencies: [f32; 3]) -> Self {
+        let mut table = WaveTable {
+            tick: f32::NAN,
+            diagonals: vec![0.0; (width + height).saturating_sub(1)],
+            columns: vec![0.0; width],
+            rows: vec![0.0; height],
+        };
+        table.fill(0.0, frequencies);
+        table
+    }
+
+    fn fill(&mut self, t: f32, [f1, f2, f3]: [f32; 3]) {
+        for (d, v) in self.diagonals.iter_mut().enumerate() {
+            *v = ((d as f32 * f1) + t).sin();
+        }
+        for (x, v) in self.columns.iter_mut().enumerate() {
+            *v = ((x as f32 * f2) - (t * 1.5)).cos();
+        }
+        for (y, v) in self.rows.iter_mut().enumerate() {
+            *v = ((y as f32 * f3) + (t * 0.5)).sin();
+        }
+        self.tick = t;
+    }
+
+    // None off the grid or between cells, where the waves are computed directly.
+    fn get(&self, x: f32, y: f32) -> Option<(f32, f32, f32)> {
+        if x.fract() != 0.0 || y.fract() != 0.0 || x < 0.0 || y < 0.0 {
+            return None;
+        }
+        let (x, y) = (x as usize, y as usize);
+        Some((
+            *self.diagonals.get(x + y)?,
+            *self.columns.get(x)?,
+            *self.rows.get(y)?,
+        ))
+    }
+}
+
+impl Water {
+    // `width` x `height` is the grid whose cells are precomputed each tick.
+    pub fn new(load: SystemLoad, config: &Config) -> Self {
+        let (width, height) = (config.grid_width, config.grid_height);
+        let params = config.water.clone();
+        Water {
+            time_tick: 0.0,
+            load,
+            table: WaveTable::new(width, height, params.frequencies),
+            params: Arc::new(params),
+            night: (config.day_night.enabled).then(|| Arc::new(config.day_night.night.clone())),
+            night_mix: Arc::new(AtomicU32::new(0.0f32.to_bits())),
+            now_playing: NowPlaying::default(),
+            now_playing_strength: config.mpris.strength,
+        }
+    }
+
+    pub fn set_night_mix(&self, mix: f32) {
+        self.night_mix.store(mix.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn system_load(&self) -> SystemLoad {
+        self.load.clone()
+    }
+
+    pub fn now_playing(&self) -> NowPlaying {
+        self.now_playing.clone()
+    }
+
+    pub fn advance(&mut self, ticks: f32) {
+        self.time_tick += self.params.speed * ticks;
+        self.table.fill(self.time_tick, self.params.frequencies);
+    }
+
+    fn waves(&self, x: f32, y: f32) -> (f32, f32, f32) {
+        let t = self.time_tick;
+        if self.table.tick == t
+            && let Some(waves) = self.table.get(x, y)
+        {
+            return waves;
+        }
+        let [f1, f2, f3] = self.params.frequencies;
+        (
+            ((x * f1) + (y * f1) + t).sin(),
+            ((x * f2) - (t * 1.5)).cos(),
+            ((y * f3) + (t * 0.5)).sin(),
+        )
+    }
+
+    // The palette color at `level`, 0.0 (first) to 1.0 (last).
+    fn palette(colors: &[Color], level: f32) -> (f32, f32, f32) {
+        let pos = level.clamp(0.0, 1.0) * (colors.len() - 1) as f32;
+        let i = pos as usize;
+        let (a, b) = (colors[i], colors[(i + 1).min(colors.len() - 1)]);
+        let mix = |a: u8, b: u8| a as f32 + (b as f32 - a as f32) * pos.fract();
+        (mix(a.r, b.r), mix(a.g, b.g), mix(a.b, b.b))
+    }
+
+    pub fn color(&self, x: f32, y: f32) -> Color {
+        let (wave1, wave2, wave3) = self.waves(x, y);
+        let combined = (wave1 + wave2 + wave3) / 3.0;
+
+        let level = (combined + 1.0) / 2.0;
+        let day = &self.params;
+        let mut brightness = day.floor + (day.depth * combined);
+        let (mut pr, mut pg, mut pb) = Self::palette(&day.palette, level);
+        let mix = f32::from_bits(self.night_mix.load(Ordering::Relaxed));
+        if let Some(night) = &self.night
+            && mix > 0.0
+        {
+            let (nr, ng, nb) = Self::palette(&night.palette, level);
+            let blend = |a: f32, b: f32| a + (b - a) * mix;
+            brightness = blend(brightness, night.floor + (night.depth * combined));
+            (pr, pg, pb) = (blend(pr, nr), blend(pg, ng), blend(pb, nb));
+        }
+
+        // Toward the track playing, then warming toward red with system load.
+        if let Some(tint) = self.now_playing.get() {
+            let k = self.now_playing_strength;
+            pr += (tint.r as f32 - pr) * k;
+            pg += (tint.g as f32 - pg) * k;
+            pb += (tint.b as f32 - pb) * k;
+        }
+        if let Some(load) = self.load.get() {
+            pr += (255.0 - pr) * load;
+            pg += (90.0 - pg) * load;
+            pb += (40.0 - pb) * load;
+        }
+        let r = (brightness * pr) as u8;
+        let g = (brightness * pg) as u8;
+        let b = (brightness * pb) as u8;
+
+        Color::new(r, g, b)
+    }
+}
+
+// --- AMBIENT ---
+struct Ripple {
+    x: f32,
+    y: f32,
+    age: f32, // in ticks
+    max_age: f32,
+    stretch: f32, // how much faster the ring grows along the row
+}
+
+pub struct Ambient {
+    config: Arc<Config>,
+    water: Water,
+    ripples: Vec<Ripple>,
+    // Per cell, when a press and a release last rang.
+    last_ripple: Vec<(Option<Instant>, Option<Instant>)>,
+    key_glow: Vec<f32>, // per cell, 1.0 right after a press
+}
+
+impl Ambient {
+    pub fn new(config: Arc<Config>, water: Water) -> Self {
+        let cells = config.grid_width * config.grid_height;
+        Ambient {
+            config,
+            water,
+            ripples: Vec::new(),
+            last_ripple: vec![(None, None); cells],
+            key_glow: vec![0.0; cells],
+        }
+    }
+
+    // Ripples are pushed in order, so the oldest is first.
+    fn push_ripple(&mut self, ripple: Ripple) {
+        let max = self.config.ripple.max_count;
+        if self.ripples.len() >= max {
+            self.ripples.drain(..=self.ripples.len() - max);
+        }
+        self.ripples.push(ripple);
+    }
+
+    fn cell(&self, x: i32, y: i32) -> Option<usize> {
+        let (w, h) = (
+            self.config.grid_width as i32,
+            self.config.grid_height as i32,
+        );
+        ((0..w).contains(&x) && (0..h).contains(&y)).then(|| (y * w + x) as usize)
+    }
+}
+
+impl Effect for Ambient {
+    fn update(&mut self, dt: f32) {
+        let ticks = dt / self.config.tick_secs();
+        self.water.advance(ticks);
+        for r in &mut self.ripples {
+            r.age += ticks;
+        }
+        self.ripples.retain(|r| r.age < r.max_age);
+        let fade = dt / self.config.key_glow.decay_secs.max(0.01);
+        for g in &mut self.key_glow {
+            *g = (*g - fade).max(0.0);
+        }
+    }
+
+    fn color_at(&self, x: i32, y: i32) -> Color {
+        let mut base = self.water.color(x as f32, y as f32);
+
+        for r in &self.ripples {
+            let rdx = (x as f32 - r.x) / r.stretch;
+            let rdy = y as f32 - r.y;
+            let r_dist = (rdx * rdx + rdy * rdy).sqrt();
+            let radius = r.age * self.config.ripple.speed;
+            let width = self.config.ripple.width;
+
+            let off_ring = (r_dist - radius).abs();
+            if off_ring < width {
+                let fade = 1.0 - (r.age / r.max_age).powf(3.0);
+                if fade <= 0.0 {
+                    continue;
+                }
+                if self.config.ripple.additive {
+                    base.r = base.r.saturating_add((fade * 255.0) as u8);
+                    base.g = base.g.saturating_add((fade * 255.0) as u8);
+                    base.b = base.b.saturating_add((fade * 255.0) as u8);
+                } else {
+                    // Soft edges, so the ring doesn't band against the water.
+                    let edge = 1.0 - off_ring / width;
+                    base = lerp_color(base, self.config.ripple.color, fade * edge);
+                }
+            }
+        }
+        if self.config.key_glow.enabled
+            && let Some(i) = self.cell(x, y)
+        {
+            base = lerp_color(base, self.config.key_glow.color, self.key_glow[i]);
+        }
+        base
+    }
+
+    fn key_down(&mut self, _code: u16, x: i32, y: i32) {
+        let min = Duration::from_millis(self.config.ripple.min_interval_ms);
+        if let Some(i) = self.cell(x, y) {
+            self.key_glow[i] = 1.0;
+            if !may_ring(&mut self.last_ripple[i].0, min) {
+                return;
+            }
+        }
+        self.push_ripple(Ripple {
+            x: x as f32,
+            y: y as f32,
+            age: 0.0,
+            max_age: self.config.ripple.max_age,
+            stretch: self.config.ripple.row_bias,
+        });
+    }
+
+    // A faint, short-lived ring where the key came back up.
+    fn key_up(&mut self, _code: u16, x: i32, y: i32) {
+        let min = Duration::from_millis(self.config.ripple.min_interval_ms);
+        if let Some(i) = self.cell(x, y)
+            && !may_ring(&mut self.last_ripple[i].1, min)
+        {
+            return;
+        }
+        self.push_ripple(Ripple {
+            x: x as f32,
+            y: y as f32,
+            age: 0.0,
+            max_age: 5.0,
+            stretch: 1.0,
+        });
+    }
+
+    fn exits_on_esc(&self) -> bool {
+        false
+    }
+}
+
+// False if the last ring at a cell, stamped in `last`, started under `min`
+// ago; otherwise stamps it now.
+fn may_ring(last: &mut Option<Instant>, min: Duration) -> bool {
+    if last.is_some_and(|at| at.elapsed() < min) {
+        return false;
+    }
+    *last = Some(Instant::now());
+    true
+}
+
+// --- GAME OVER ---
+// Flashes for a second, in the winner's color after a two-player game, then
+// shows the score (or scrolls the text in attract mode) until it hands back
+// to ambient.
+pub struct GameOver {
+    config: Arc<Config>,
+    text: String,
+    score: String,
+    new_best: bool,
+    winner: Option<Color>,
+    elapsed: f32, // seconds
+}
+
+impl GameOver {
+    pub fn new(config: Arc<Config>, score: usize, new_best: bool, winner: Option<Color>) -> Self {
+        let mut text = format!("{} {}", config.game_over.text, score);
+        if new_best {
+            text.push_str(" BEST");
+        }
+        GameOver {
+            config,
+            text,
+            score: score.to_string(),
+            new_best,
+            winner,
+            elapsed: 0.0,
+        }
+    }
+
+    fn duration(&self) -> f32 {
+        if self.config.game_over.attract {
+            self.config.game_over.attract_secs
+        } else {
+            self.config.game_over.secs
+        }
+    }
+
+    // Scrolls the game-over text right to left over the rest of the attract time.
+    fn attract_color(&self, x: i32, y: i32, secs: f32) -> Color {
+        let (w, h) = (
+            self.config.grid_width as i32,
+            self.config.grid_height as i32,
+        );
+        let travel = (w + font::text_width(&self.text)) as f32;
+        let speed = travel / (self.config.game_over.attract_secs - 1.0).max(0.1);
+        if font::scrolled_lit(&self.text, x, y, secs * speed, w, h) {
+            self.config.game_over.color
+        } else {
+            Color::new(10, 0, 0)
+        }
+    }
+}
+
+impl Effect for GameOver {
+    fn update(&mut self, dt: f32) {
+        self.elapsed += dt;
+    }
+
+    fn color_at(&self, x: i32, y: i32) -> Color {
+        let elapsed_ms = (self.elapsed * 1000.0) as u128;
+        if self.config.game_over.attract && elapsed_ms >= 1000 {
+            return self.attract_color(x, y, (elapsed_ms - 1000) as f32 / 1000.0);
+        }
+        let game_over = &self.config.game_over;
+        if elapsed_ms < 1000 {
+            return if (elapsed_ms / game_over.blink_ms as u128).is_multiple_of(2) {
+                self.winner.unwrap_or(game_over.color)
+            } else {
+                game_over.blink_color
+            };
+        }
+        // Then the score, gold when it beat the stored best.
+        let (w, h) = (
+            self.config.grid_width as i32,
+            self.config.grid_height as i32,
+        );
+        let left = (w - font::text_width(&self.score)) / 2;
+        let top = (h - font::GLYPH_H) / 2;
+        if !font::text_lit(&self.score, x - left, y - top) {
+            Color::new(10, 0, 0)
+        } else if self.new_best {
+            Color::new(255, 180, 0)
+        } else {
+            game_over.color
+        }
+    }
+
+    fn next_mode(&self) -> Option<Mode> {
+        (self.elapsed >= self.duration()).then_some(Mode::Ambient)
+    }
+
+    fn exits_on_esc(&self) -> bool {
+        false
+    }
+}
+
+// --- RAINBOW ---
+// Fully saturated hues sweeping across the grid. Working in HSV keeps every
+// color clean without holding channels back.
+pub struct Rainbow {
+    config: Arc<Config>,
+    hue: f32, // degrees at column 0
+}
+
+impl Rainbow {
+    pub fn new(config: Arc<Config>) -> Self {
+        Rainbow { config, hue: 0.0 }
+    }
+
+    // `across` runs 0.0..1.0 over the grid or a device's LEDs; `hue` is the
+    // hue at its start. Blend and the rainbow device effect draw this too.
+    pub fn color(config: &Config, hue: f32, across: f32) -> Color {
+        Color::from_hsv(hue + across * config.rainbow.spread * 360.0, 1.0, 1.0)
+    }
+
+    // The hue at the start after `secs` of running.
+    pub fn hue(config: &Config, secs: f32) -> f32 {
+        (secs * config.rainbow.speed * 360.0).rem_euclid(360.0)
+    }
+}
+
+impl Effect for Rainbow {
+    fn update(&mut self, dt: f32) {
+        self.hue = (self.hue + dt * self.config.rainbow.speed * 360.0).rem_euclid(360.0);
+    }
+
+    fn color_at(&self, x: i32, _y: i32) -> Color {
+        let across = x as f32 / self.config.grid_width as f32;
+        Rainbow::color(&self.config, self.hue, across)
+    }
+}
+
+// --- MATRIX ---
+// Drops falling down each column, a near-white head trailing `matrix.tail`
+// rows of fading color.
+struct Raindrop {
+    y: f32,     // rows down from the top, negative while waiting to fall
+    speed: f32, // rows per second
+}
+
+pub struct Matrix {
+    config: Arc<Config>,
+    rain: Vec<Raindrop>, // one per column
+    rng: StdRng,
+}
+
+impl Matrix {
+    pub fn new(config: Arc<Config>, rng: StdRng) -> Self {
+        let mut matrix = Matrix {
+            config,
+            rain: Vec::new(),
+            rng,
+        };
+        matrix.rain = (0..matrix.config.grid_width)
+            .map(|_| matrix.new_raindrop())
+            .collect();
+        matrix
+    }
+
+    // Starts above the top, further up the sparser the rain, so columns
+    // take turns falling.
+    fn new_raindrop(&mut self) -> Raindrop {
+        let cfg = &self.config.matrix;
+        let wait = self.rng.random_range(0.0..1.0) / cfg.density - 1.0;
+        Raindrop {
+            y: -wait.max(0.0) * self.config.grid_height as f32 * 2.0,
+            speed: cfg.speed * self.rng.random_range(0.5..1.5),
+        }
+    }
+}
+
+impl Effect for Matrix {
+    fn update(&mut self, dt: f32) {
+        let bottom = self.config.grid_height as f32 + self.config.matrix.tail;
+        for x in 0..self.rain.len() {
+            self.rain[x].y += self.rain[x].speed * dt;
+            if self.rain[x].y > bottom {
+                self.rain[x] = self.new_raindrop();
+            }
+        }
+    }
+
+    fn color_at(&self, x: i32, y: i32) -> Color {
+        let cfg = &self.config.matrix;
+        let Some(drop) = self.rain.get(x as usize) else {
+            return Color::new(0, 0, 0);
+        };
+        let behind = drop.y - y as f32;
+        if (0.0..1.0).contains(&behind) {
+            lerp_color(cfg.color, Color::new(255, 255, 255), 0.6)
+        } else if behind >= 1.0 && behind < cfg.tail {
+            scale_color(cfg.color, 1.0 - behind / cfg.tail)
+        } else {
+            Color::new(0, 0, 0)
+        }
+    }
+}
+
+// --- SHARED ---
+// State kept up from outside the effects: key heat from `AppState`, audio
+// bands from the cava task, the pomodoro timer. Shared by every clone;
+// effects copy it out in `update` rather than locking per LED.
+#[derive(Clone, Default)]
+pub struct Shared<T>(Arc<Mutex<T>>);
+
+impl<T: Clone> Shared<T> {
+    pub fn new(value: T) -> Self {
+        Shared(Arc::new(Mutex::new(value)))
+    }
+
+    pub fn get(&self) -> T {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, value: T) {
+        *self.0.lock().unwrap() = value;
+    }
+
+    pub fn modify<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.0.lock().unwrap())
+    }
+}
+
+// --- HEATMAP ---
+// Blue for rarely pressed keys through to red for the most pressed one.
+pub struct Heatmap {
+    config: Arc<Config>,
+    heat: Shared<Vec<f32>>,
+    cells: Vec<f32>, // `heat` as of the last update
+    hottest: f32,
+}
+
+impl Heatmap {
+    pub fn new(config: Arc<Config>, heat: Shared<Vec<f32>>) -> Self {
+        let mut heatmap = Heatmap {
+            config,
+            heat,
+            cells: Vec::new(),
+            hottest: 0.0,
+        };
+        heatmap.update(0.0);
+        heatmap
+    }
+}
+
+impl Effect for Heatmap {
+    fn update(&mut self, _dt: f32) {
+        self.cells = self.heat.get();
+        self.hottest = self.cells.iter().copied().fold(0.0, f32::max);
+    }
+
+    fn color_at(&self, x: i32, y: i32) -> Color {
+        let w = self.config.grid_width as i32;
+        let heat = self.cells.get((y * w + x) as usize).copied().unwrap_or(0.0);
+        if self.hottest <= 0.0 {
+            return Color::new(0, 0, 40);
+        }
+        let t = heat / self.hottest;
+        let c = lerp_color(Color::new(0, 0, 255), Color::new(255, 0, 0), t);
+        scale_color(c, 0.2 + 0.8 * t)
+    }
+}
+
+// --- AUDIO ---
+// One bar per band spread across the columns, green at the bottom to red at
+// the top. Shows the water until cava starts writing.
+pub struct Audio {
+    config: Arc<Config>,
+    water: Water,
+    levels: Shared<Vec<f32>>,
+    bands: Vec<f32>, // 0.0..=1.0, low to high frequency
+}
+
+impl Audio {
+    pub fn new(config: Arc<Config>, water: Water, levels: Shared<Vec<f32>>) -> Self {
+        Audio {
+            config,
+            water,
+            levels,
+            bands: Vec::new(),
+        }
+    }
+}
+
+impl Effect for Audio {
+    fn update(&mut self, dt: f32) {
+        self.water.advance(dt / self.config.tick_secs());
+        self.bands = self.levels.get();
+    }
+
+    fn color_at(&self, x: i32, y: i32) -> Color {
+        if self.bands.is_empty() {
+            return self.water.color(x as f32, y as f32);
+        }
+        let (w, h) = (
+            self.config.grid_width as i32,
+            self.config.grid_height as i32,
+        );
+        let band = (x as usize * self.bands.len() / w as usize).min(self.bands.len() - 1);
+        let level = self.bands[band] * h as f32;
+        let row = (h - 1 - y) as f32; // 0 at the bottom
+        if row >= level {
+            return Color::new(0, 0, 10);
+        }
+        let t = row / (h - 1).max(1) as f32;
+        let lit = lerp_color(Color::new(0, 255, 0), Color::new(255, 0, 0), t);
+        // The partly filled top cell of a bar is dimmer.
+        lerp_color(Color::new(0, 0, 10), lit, (level - row).min(1.0))
+    }
+}
+
+// --- POMODORO ---
+// Work and break phases counting down. `AppState` keeps the timer running
+// outside pomodoro mode, so the end of a phase still flashes over whatever
+// is shown.
+#[derive(Clone)]
+pub struct PomodoroTimer {
+    pub working: bool,
+    pub remaining: f32, // seconds left in this phase
+    pub running: bool,
+}
+
+impl PomodoroTimer {
+    pub fn new(config: &Config) -> Self {
+        PomodoroTimer {
+            working: true,
+            remaining: config.pomodoro.work_mins * 60.0,
+            running: false,
+        }
+    }
+
+    fn phase_secs(&self, config: &Config) -> f32 {
+        let cfg = &config.pomodoro;
+        60.0 * if self.working {
+            cfg.work_mins
+        } else {
+            cfg.break_mins
+        }
+    }
+
+    pub fn color(&self, config: &Config) -> Color {
+        if self.working {
+            config.pomodoro.work_color
+        } else {
+            config.pomodoro.break_color
+        }
+    }
+
+    // Counts down, switching phase at zero. Returns true when it did.
+    pub fn advance(&mut self, dt: f32, config: &Config) -> bool {
+        if !self.running {
+            return false;
+        }
+        self.remaining -= dt;
+        if self.remaining > 0.0 {
+            return false;
+        }
+        self.working = !self.working;
+        self.remaining = self.phase_secs(config);
+        true
+    }
+}
+
+// A bar of the phase color shrinking from the right, dimmer while paused,
+// with the minutes left over it. Space starts and stops the timer, R resets
+// it.
+pub struct Pomodoro {
+    config: Arc<Config>,
+    timer: Shared<PomodoroTimer>,
+    shown: PomodoroTimer, // `timer` as of the last update
+    minutes: String,
+}
+
+impl Pomodoro {
+    pub fn new(config: Arc<Config>, timer: Shared<PomodoroTimer>) -> Self {
+        let mut pomodoro = Pomodoro {
+            shown: timer.get(),
+            config,
+            timer,
+            minutes: String::new(),
+        };
+        pomodoro.update(0.0);
+        pomodoro
+    }
+}
+
+impl Effect for Pomodoro {
+    fn update(&mut self, _dt: f32) {
+        self.shown = self.timer.get();
+        self.minutes = ((self.shown.remaining / 60.0).ceil() as u32).to_string();
+    }
+
+    fn color_at(&self, x: i32, y: i32) -> Color {
+        let (w, h) = (
+            self.config.grid_width as i32,
+            self.config.grid_height as i32,
+        );
+        let timer = &self.shown;
+        let left = (w - font::text_width(&self.minutes)) / 2;
+        let top = (h - font::GLYPH_H) / 2;
+        if font::text_lit(&self.minutes, x - left, y - top) {
+            return timer.color(&self.config);
+        }
+        let share = timer.remaining / timer.phase_secs(&self.config);
+        if x as f32 >= (share * w as f32).ceil() {
+            return Color::new(0, 0, 0);
+        }
+        let dim = if timer.running { 0.3 } else { 0.1 };
+        scale_color(timer.color(&self.config), dim)
+    }
+
+    fn key_down(&mut self, code: u16, _x: i32, _y: i32) {
+        match code {
+            KEY_SPACE => self.timer.modify(|t| t.running = !t.running),
+            KEY_R => self.timer.set(PomodoroTimer::new(&self.config)),
+            _ => {}
+        }
+        self.update(0.0);
+    }
+}
+
+// --- LIFE ---
+// Conway's game of life on the grid with the edges wrapping around. Keys
+// plant a live cell; a board that dies out or settles into a still life or
+// short oscillator is reseeded.
+pub struct Life {
+    config: Arc<Config>,
+    water: Water,            // glows through the live cells
+    cells: Vec<bool>,        // row-major, width x height
+    history: Vec<Vec<bool>>, // the two previous generations
+    since_step: f32,         // seconds
+    rng: StdRng,
+}
+
+impl Life {
+    const STEP_SECS: f32 = 0.2;
+
+    pub fn new(config: Arc<Config>, water: Water, rng: StdRng) -> Self {
+        let mut life = Life {
+            config,
+            water,
+            cells: Vec::new(),
+            history: Vec::new(),
+            since_step: 0.0,
+            rng,
+        };
+        life.seed();
+        life
+    }
+
+    fn size(&self) -> (i32, i32) {
+        (
+            self.config.grid_width as i32,
+            self.config.grid_height as i32,
+        )
+    }
+
+    fn seed(&mut self) {
+        let (w, h) = self.size();
+        let rng = &mut self.rng;
+        self.cells = (0..w * h).map(|_| rng.random_bool(0.35)).collect();
+        self.history.clear();
+    }
+
+    fn step(&mut self) {
+        let (w, h) = self.size();
+        let alive = |x: i32, y: i32| self.cells[(y.rem_euclid(h) * w + x.rem_euclid(w)) as usize];
+        let mut next = Vec::with_capacity(self.cells.len());
+        for y in 0..h {
+            for x in 0..w {
+                let mut n = 0;
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        if (dx, dy) != (0, 0) && alive(x + dx, y + dy) {
+                            n += 1;
+                        }
+                    }
+                }
+                next.push(n == 3 || (n == 2 && alive(x, y)));
+            }
+        }
+
+        let stale = !next.contains(&true) || next == self.cells || self.history.contains(&next);
+        self.history.push(std::mem::replace(&mut self.cells, next));
+        if self.history.len() > 2 {
+            self.history.remove(0);
+        }
+        if stale {
+            self.seed();
+        }
+    }
+}
+
+impl Effect for Life {
+    fn update(&mut self, dt: f32) {
+        self.water.advance(dt / self.config.tick_secs());
+        self.since_step += dt;
+        if self.since_step >= Life::STEP_SECS {
+            self.since_step = 0.0;
+            self.step();
+        }
+    }
+
+    fn color_at(&self, x: i32, y: i32) -> Color {
+        let (w, _) = self.size();
+        if self.cells[(y * w + x) as usize] {
+            let glow = self.water.color(x as f32, y as f32);
+            Color::new(glow.r / 2, 255, glow.b / 2 + 100)
+        } else {
+            Color::new(0, 8, 4)
+        }
+    }
+
+    fn key_down(&mut self, _code: u16, x: i32, y: i32) {
+        let (w, h) = self.size();
+        if (0..w).contains(&x) && (0..h).contains(&y) {
+            self.cells[(y * w + x) as usize] = true;
+        }
+    }
+}
+
+// --- TEXT ---
+// A message scrolling right to left, starting over once it has left the grid.
+pub struct Text {
+    config: Arc<Config>,
+    message: String,
+    scroll: f32, // cells
+}
+
+impl Text {
+    pub fn new(config: Arc<Config>, message: String) -> Self {
+        Text {
+            config,
+            message,
+            scroll: 0.0,
+        }
+    }
+}
+
+impl Effect for Text {
+    fn update(&mut self, dt: f32) {
+        let travel = (self.config.grid_width as i32 + font::text_width(&self.message)) as f32;
+        self.scroll = (self.scroll + dt * self.config.text.speed).rem_euclid(travel.max(1.0));
+    }
+
+    fn color_at(&self, x: i32, y: i32) -> Color {
+        let (w, h) = (
+            self.config.grid_width as i32,
+            self.config.grid_height as i32,
+        );
+        if font::scrolled_lit(&self.message, x, y, self.scroll, w, h) {
+            self.config.text.color
+        } else {
+            self.config.text.background
+        }
+    }
+}
+
+// --- CLOCK ---
+// The local time as HH:MM, with the colon blinking each second.
+pub struct Clock {
+    config: Arc<Config>,
+    text: String,
+}
+
+impl Clock {
+    pub fn new(config: Arc<Config>) -> Self {
+        let mut clock = Clock {
+            config,
+            text: String::new(),
+        };
+        clock.update(0.0);
+        clock
+    }
+
+    fn format(&self, (h, m, s): (u32, u32, u32)) -> String {
+        let colon = if s % 2 == 0 { ':' } else { ' ' };
+        if self.config.clock.twelve_hour {
+            format!("{:>2}{}{:02}", (h + 11) % 12 + 1, colon, m)
+        } else {
+            format!("{:02}{}{:02}", h, colon, m)
+        }
+    }
+}
+
+// Wall-clock time in the system's time zone.
+pub fn local_time() -> (u32, u32, u32) {
+    let now = chrono::Local::now();
+    (now.hour(), now.minute(), now.second())
+}
+
+impl Effect for Clock {
+    fn update(&mut self, _dt: f32) {
+        self.text = self.format(local_time());
+    }
+
+    fn color_at(&self, x: i32, y: i32) -> Color {
+        let text = &self.text;
+        let (w, h) = (
+            self.config.grid_width as i32,
+            self.config.grid_height as i32,
+        );
+        let left = (w - font::text_width(text)) / 2;
+        let top = (h - font::GLYPH_H) / 2;
+        if font::text_lit(text, x - left, y - top) {
+            self.config.clock.color
+        } else {
+            self.config.clock.background
+        }
+    }
+}
+
+// --- SUNRISE ---
+// A slow wake-up ramp from dark red through orange to full white. Any key
+// ends it early.
+pub struct Sunrise {
+    config: Arc<Config>,
+    progress: f32, // 0.0..=1.0
+    done: bool,
+}
+
+impl Sunrise {
+    pub fn new(config: Arc<Config>) -> Self {
+        Sunrise {
+            config,
+            progress: 0.0,
+            done: false,
+        }
+    }
+
+    fn color(progress: f32) -> Color {
+        let stops = [
+            Color::new(0, 0, 0),
+            Color::new(60, 0, 0),
+            Color::new(255, 90, 0),
+            Color::new(255, 200, 120),
+            Color::new(255, 255, 255),
+        ];
+        let t = progress.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let i = (t as usize).min(stops.len() - 2);
+        lerp_color(stops[i], stops[i + 1], t - i as f32)
+    }
+}
+
+impl Effect for Sunrise {
+    fn update(&mut self, dt: f32) {
+        self.progress += dt / (self.config.sunrise.duration_mins * 60.0);
+    }
+
+    fn color_at(&self, _x: i32, _y: i32) -> Color {
+        Sunrise::color(self.progress)
+    }
+
+    fn key_down(&mut self, _code: u16, _x: i32, _y: i32) {
+        self.done = true;
+    }
+
+    fn next_mode(&self) -> Option<Mode> {
+        (self.done || self.progress >= 1.0).then_some(Mode::Ambient)
+    }
+}
+
+// --- SOLID ---
+// One steady color everywhere. Frames never change, so it is sent once.
+pub struct Solid {
+    config: Arc<Config>,
+    color: Color,
+}
+
+impl Solid {
+    pub fn new(config: Arc<Config>, color: Color) -> Self {
+        Solid { config, color }
+    }
+}
+
+impl Effect for Solid {
+    fn update(&mut self, _dt: f32) {}
+
+    fn color_at(&self, _x: i32, _y: i32) -> Color {
+        self.color
+    }
+
+    // Space steps through `solid.colors`.
+    fn key_down(&mut self, code: u16, _x: i32, _y: i32) {
+        let colors = &self.config.solid.colors;
+        if code == KEY_SPACE {
+            let next = colors
+                .iter()
+                .position(|&c| c == self.color)
+                .map_or(0, |i| i + 1);
+            self.color = colors[next % colors.len()];
+        }
+    }
+}
+
+// --- BREATHE ---
+// One color fading from dark to full and back every `breathe.period_secs`.
+pub struct Breathe {
+    config: Arc<Config>,
+    color: Color,
+    secs: f32, // into the cycle
+}
+
+impl Breathe {
+    pub fn new(config: Arc<Config>, color: Color) -> Self {
+        Breathe {
+            config,
+            color,
+            secs: 0.0,
+        }
+    }
+
+    // The breathe device effect draws this too.
+    pub fn color(config: &Config, color: Color, secs: f32) -> Color {
+        let phase = secs / config.breathe.period_secs * std::f32::consts::TAU;
+        scale_color(color, 0.5 - 0.5 * phase.cos())
+    }
+}
+
+impl Effect for Breathe {
+    fn update(&mut self, dt: f32) {
+        self.secs = (self.secs + dt) % self.config.breathe.period_secs;
+    }
+
+    fn color_at(&self, _x: i32, _y: i32) -> Color {
+        Breathe::color(&self.config, self.color, self.secs)
+    }
+}
+
+// --- METRONOME ---
+// A column sweeping across the grid once per beat at `metronome.bpm`, the
+// beat landing as it wraps back to column 0. Keys pressed close enough to a
+// beat flash green, the rest red; the session's score is logged on the way
+// out.
+struct Beat {
+    x: f32,
+    y: f32,
+    hit: bool,
+    age: f32, // seconds
+}
+
+impl Beat {
+    const FADE_SECS: f32 = 0.3;
+}
+
+pub struct Metronome {
+    config: Arc<Config>,
+    water: Water,
+    phase: f32, // 0.0..1.0 of the way from one beat to the next
+    beats: Vec<Beat>,
+    hits: u32,
+    misses: u32,
+}
+
+impl Metronome {
+    pub fn new(config: Arc<Config>, water: Water) -> Self {
+        Metronome {
+            config,
+            water,
+            phase: 0.0,
+            beats: Vec::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+}
+
+impl Effect for Metronome {
+    fn update(&mut self, dt: f32) {
+        self.water.advance(dt / self.config.tick_secs());
+        self.phase = (self.phase + self.config.metronome.bpm / 60.0 * dt) % 1.0;
+        for b in &mut self.beats {
+            b.age += dt;
+        }
+        self.beats.retain(|b| b.age < Beat::FADE_SECS);
+    }
+
+    fn color_at(&self, x: i32, y: i32) -> Color {
+        for b in &self.beats {
+            if b.x as i32 == x && b.y as i32 == y {
+                let fade = 1.0 - b.age / Beat::FADE_SECS;
+                let v = (fade * 255.0) as u8;
+                return if b.hit {
+                    Color::new(0, v, 0)
+                } else {
+                    Color::new(v, 0, 0)
+                };
+            }
+        }
+        let sweep_x = self.phase * self.config.grid_width as f32;
+        let dist = (x as f32 - sweep_x).abs();
+        if dist < 1.0 {
+            let v = ((1.0 - dist) * 255.0) as u8;
+            return Color::new(v, v, v);
+        }
+        Color::new(5, 5, 15)
+    }
+
+    fn key_down(&mut self, _code: u16, x: i32, y: i32) {
+        // How far off the nearest beat, early or late.
+        let off = self.phase.min(1.0 - self.phase) * 60.0 / self.config.metronome.bpm;
+        let hit = off * 1000.0 <= self.config.metronome.hit_window_ms;
+        if hit {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+        self.beats.push(Beat {
+            x: x as f32,
+            y: y as f32,
+            hit,
+            age: 0.0,
+        });
+    }
+
+    fn score(&self) -> Option<usize> {
+        Some(self.hits as usize)
+    }
+
+    fn leave(&mut self) {
+        info!("Metronome: {}/{} hits", self.hits, self.hits + self.misses);
+    }
+}
+
+// --- SCREEN ---
+// The screen's colors column by column, from the capture task. Shows the
+// water until the first capture lands.
+pub struct Screen {
+    config: Arc<Config>,
+    water: Water,
+    captured: Shared<Vec<Color>>,
+    columns: Vec<Color>, // `captured` as of the last update
+}
+
+impl Screen {
+    pub fn new(config: Arc<Config>, water: Water, captured: Shared<Vec<Color>>) -> Self {
+        Screen {
+            config,
+            water,
+            captured,
+            columns: Vec::new(),
+        }
+    }
+}
+
+impl Effect for Screen {
+    fn update(&mut self, dt: f32) {
+        self.water.advance(dt / self.config.tick_secs());
+        self.columns = self.captured.get();
+    }
+
+    fn color_at(&self, x: i32, y: i32) -> Color {
+        if self.columns.is_empty() {
+            return self.water.color(x as f32, y as f32);
+        }
+        let col = x as usize * self.columns.len() / self.config.grid_width;
+        self.columns[col.min(self.columns.len() - 1)]
+    }
+}
+
+// --- BLEND ---
+// Two of the stateless ambient effects mixed, `mix` of the way from `a` to
+// `b`.
+pub struct Blend {
+    config: Arc<Config>,
+    water: Water,
+    layers: (AmbientEffect, AmbientEffect),
+    mix: f32,
+    secs: f32, // for the rainbow's hue
+}
+
+impl Blend {
+    pub fn new(
+        config: Arc<Config>,
+        water: Water,
+        a: AmbientEffect,
+        b: AmbientEffect,
+        mix: f32,
+    ) -> Self {
+        Blend {
+            config,
+            water,
+            layers: (a, b),
+            mix,
+            secs: 0.0,
+        }
+    }
+
+    fn layer(&self, effect: AmbientEffect, x: i32, y: i32) -> Color {
+        match effect {
+            AmbientEffect::Water => self.water.color(x as f32, y as f32),
+            AmbientEffect::Rainbow => Rainbow::color(
+                &self.config,
+                Rainbow::hue(&self.config, self.secs),
+                x as f32 / self.config.grid_width as f32,
+            ),
+        }
+    }
+}
+
+impl Effect for Blend {
+    fn update(&mut self, dt: f32) {
+        self.water.advance(dt / self.config.tick_secs());
+        self.secs += dt;
+    }
+
+    fn color_at(&self, x: i32, y: i32) -> Color {
+        let (a, b) = self.layers;
+        lerp_color(self.layer(a, x, y), self.layer(b, x, y), self.mix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The formula `Water` used before the wave table.
+    fn direct(water: &Water, x: f32, y: f32) -> Color {
+        let mut uncached = water.clone();
+        uncached.table.tick = f32::NAN;
+        uncached.color(x, y)
+    }
+
+    #[test]
+    fn wave_table_matches_the_formula() {
+        let mut water = Water::new(SystemLoad::default(), &Config::default());
+        for _ in 0..200 {
+            water.advance(1.0);
+            for y in 0..6 {
+                for x in 0..22 {
+                    let (a, b) = (
+                        water.color(x as f32, y as f32),
+                        direct(&water, x as f32, y as f32),
+                    );
+                    let close = |a: u8, b: u8| a.abs_diff(b) <= 1;
+                    assert!(close(a.r, b.r) && close(a.g, b.g) && close(a.b, b.b));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fast_presses_on_one_cell_ring_once() {
+        let config = Arc::new(Config::default());
+        let mut ambient = Ambient::new(config.clone(), Water::new(SystemLoad::default(), &config));
+        ambient.key_down(0, 3, 2);
+        ambient.key_down(0, 3, 2);
+        ambient.key_down(0, 4, 2);
+        assert_eq!(ambient.ripples.len(), 2);
+
+        // Mashing: each press then release rings only the first time.
+        let mut ambient = Ambient::new(config.clone(), Water::new(SystemLoad::default(), &config));
+        for _ in 0..5 {
+            ambient.key_down(0, 3, 2);
+            ambient.key_up(0, 3, 2);
+        }
+        assert_eq!(ambient.ripples.len(), 2);
+    }
+
+    #[test]
+    fn ripples_past_the_cap_replace_the_oldest() {
+        let mut config = Config::default();
+        config.ripple.max_count = 4;
+        let config = Arc::new(config);
+        let mut ambient = Ambient::new(config.clone(), Water::new(SystemLoad::default(), &config));
+        for x in 0..10 {
+            ambient.key_down(0, x, 0);
+        }
+        let xs: Vec<f32> = ambient.ripples.iter().map(|r| r.x).collect();
+        assert_eq!(xs, [6.0, 7.0, 8.0, 9.0]);
+    }
+
+    #[test]
+    fn presses_are_timed_against_the_nearest_beat() {
+        let config = Arc::new(Config::default()); // 60 bpm, 70ms either side
+        let mut metronome =
+            Metronome::new(config.clone(), Water::new(SystemLoad::default(), &config));
+        metronome.update(0.95); // 50ms early for the next beat
+        metronome.key_down(0, 0, 0);
+        metronome.update(0.1); // 50ms late
+        metronome.key_down(0, 21, 5);
+        metronome.update(0.45); // half a beat off
+        metronome.key_down(0, 0, 0);
+        assert_eq!((metronome.hits, metronome.misses), (2, 1));
+    }
+
+    // cargo test --release -- --ignored water_benchmark
+    #[test]
+    #[ignore]
+    fn water_benchmark() {
+        let mut water = Water::new(SystemLoad::default(), &Config::default());
+        let frames = 20_000;
+        let mut sink = 0u32;
+        let mut run = |cached: bool, water: &mut Water| {
+            let start = Instant::now();
+            for _ in 0..frames {
+                water.advance(1.0);
+                if !cached {
+                    water.table.tick = f32::NAN;
+                }
+                for y in 0..6 {
+                    for x in 0..22 {
+                        sink += water.color(x as f32, y as f32).r as u32;
+                    }
+                }
+            }
+            start.elapsed()
+        };
+        let direct = run(false, &mut water);
+        let table = run(true, &mut water);
+        std::hint::black_box(sink);
+        assert!(
+            table < direct,
+            "table {:?} is no faster than direct {:?}",
+            table,
+            direct
+        );
+    }
+}
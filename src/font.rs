@@ -0,0 +1,122 @@
+// rgb ctrl
+// Copyright (C) 2025 mari
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 2 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+// Tiny 3x5 bitmap font. Each row is 3 bits, MSB on the left.
+
+pub const GLYPH_W: i32 = 3;
+pub const GLYPH_H: i32 = 5;
+const ADVANCE: i32 = GLYPH_W + 1;
+
+pub fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        _ => [0b111, 0b001, 0b010, 0b000, 0b010], // '?'
+    }
+}
+
+// Width in cells of `text` with one blank column between glyphs.
+pub fn text_width(text: &str) -> i32 {
+    (text.chars().count() as i32 * ADVANCE - 1).max(0)
+}
+
+// Whether cell (x, y) of a `w` x `h` grid is lit with `text` vertically
+// centered and `scroll` cells in from the right edge.
+pub fn scrolled_lit(text: &str, x: i32, y: i32, scroll: f32, w: i32, h: i32) -> bool {
+    let left = w - scroll as i32;
+    let top = (h - GLYPH_H) / 2;
+    text_lit(text, x - left, y - top)
+}
+
+// Whether cell (x, y) is lit when `text` is drawn with its top-left at (0, 0).
+pub fn text_lit(text: &str, x: i32, y: i32) -> bool {
+    if x < 0 || !(0..GLYPH_H).contains(&y) {
+        return false;
+    }
+    let (idx, col) = (x / ADVANCE, x % ADVANCE);
+    if col >= GLYPH_W {
+        return false;
+    }
+    let Some(c) = text.chars().nth(idx as usize) else {
+        return false;
+    };
+    glyph(c)[y as usize] & (0b100 >> col) != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_123() {
+        // Drawn one cell in from the left of a 22 x 6 grid.
+        let lit = |x: i32, y: i32| text_lit("123", x - 1, y);
+        let rows: Vec<String> = (0..6)
+            .map(|y| (0..13).map(|x| if lit(x, y) { '#' } else { '.' }).collect())
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                "..#..###.###.",
+                ".##....#...#.",
+                "..#..###.###.",
+                "..#..#.....#.",
+                ".###.###.###.",
+                ".............",
+            ]
+        );
+        assert!((0..6).all(|y| (13..22).all(|x| !lit(x, y))));
+    }
+}
@@ -0,0 +1,139 @@
+// rgb ctrl
+// Copyright (C) 2025 mari
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 2 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+// A tiny HTTP/1.1 API for tools that would rather not speak to a socket:
+//
+//   $ curl -X POST localhost:8080/mode/snake
+//   {"mode":"snake","brightness":1.00}
+//
+// One request per connection; bodies are ignored.
+
+use crate::{AppState, control};
+use log::{info, warn};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+// Longest request line or header we bother reading.
+const MAX_LINE: usize = 8 * 1024;
+
+fn state_json(state: &AppState) -> String {
+    format!(
+        "{{\"mode\":\"{}\",\"brightness\":{:.2}}}",
+        state.mode.name(),
+        state.brightness
+    )
+}
+
+fn error_json(message: &str) -> String {
+    let mut escaped = String::with_capacity(message.len());
+    for c in message.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    format!("{{\"error\":\"{}\"}}", escaped)
+}
+
+// Status line and JSON body for one request.
+fn route(state: &mut AppState, method: &str, path: &str) -> (&'static str, String) {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let result = match (method, segments.as_slice()) {
+        ("GET", ["state"]) => Ok(()),
+        ("GET", ["keys"]) => return ("200 OK", state.key_totals_json()),
+        ("POST", ["mode", name]) => control::set_mode(state, name),
+        ("POST", ["brightness", level]) => control::set_brightness(state, level),
+        (_, ["state"] | ["keys"] | ["mode", _] | ["brightness", _]) => {
+            return ("405 Method Not Allowed", error_json("method not allowed"));
+        }
+        _ => return ("404 Not Found", error_json("not found")),
+    };
+    match result {
+        Ok(()) => ("200 OK", state_json(state)),
+        Err(e) => ("400 Bad Request", error_json(&e)),
+    }
+}
+
+// Reads the request line and skips the headers, which nothing here needs.
+// Returns the method and the path without its query, or None when the
+// request line is malformed.
+pub async fn read_request(
+    reader: impl AsyncRead + Unpin,
+) -> std::io::Result<Option<(String, String)>> {
+    let mut reader = BufReader::new(reader).take(MAX_LINE as u64);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    loop {
+        let mut header = String::new();
+        reader.set_limit(MAX_LINE as u64);
+        if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    Ok(match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => {
+            let path = target.split('?').next().unwrap_or(target);
+            Some((method.to_string(), path.to_string()))
+        }
+        _ => None,
+    })
+}
+
+// Writes the whole response and closes the connection.
+pub async fn respond(
+    mut writer: impl AsyncWrite + Unpin,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> std::io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}\n",
+        status,
+        content_type,
+        body.len() + 1,
+        body
+    );
+    writer.write_all(response.as_bytes()).await?;
+    writer.shutdown().await
+}
+
+async fn serve_client(stream: TcpStream, state: Arc<Mutex<AppState>>) -> std::io::Result<()> {
+    let (reader, writer) = stream.into_split();
+    let (status, body) = match read_request(reader).await? {
+        Some((method, path)) => route(&mut state.lock().unwrap(), &method, &path),
+        None => ("400 Bad Request", error_json("malformed request")),
+    };
+    respond(writer, status, "application/json", &body).await
+}
+
+pub async fn serve(addr: &str, state: Arc<Mutex<AppState>>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("HTTP API listening on {}", addr);
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_client(stream, state).await {
+                warn!("HTTP client error: {}", e);
+            }
+        });
+    }
+}
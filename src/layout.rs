@@ -0,0 +1,195 @@
+// rgb ctrl
+// Copyright (C) 2025 mari
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 2 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+// Where each physical key sits on the 22x6 effect grid. The same table maps
+// evdev keycodes (input) and OpenRGB LED names (output), so a ripple started
+// by a key lands on that key's LED whatever order the controller uses.
+//
+// openrgb2 keeps the zone matrix map private, so keyboards are laid out by
+// their LED names ("Key: A", ...) instead.
+
+use openrgb2::{Color, Controller};
+
+// (evdev code, OpenRGB name without the "Key: " prefix, x, y)
+const KEYS: &[(u16, &str, i32, i32)] = &[
+    // Row 0
+    (1, "Escape", 0, 0),
+    (59, "F1", 2, 0),
+    (60, "F2", 3, 0),
+    (61, "F3", 4, 0),
+    (62, "F4", 5, 0),
+    (63, "F5", 6, 0),
+    (64, "F6", 7, 0),
+    (65, "F7", 8, 0),
+    (66, "F8", 9, 0),
+    (67, "F9", 10, 0),
+    (68, "F10", 11, 0),
+    (87, "F11", 12, 0),
+    (88, "F12", 13, 0),
+    (99, "Print Screen", 15, 0),
+    (70, "Scroll Lock", 16, 0),
+    (119, "Pause/Break", 17, 0),
+    (69, "Num Lock", 18, 0),
+    (98, "Number Pad /", 19, 0),
+    (55, "Number Pad *", 20, 0),
+    (74, "Number Pad -", 21, 0),
+    // Row 1
+    (41, "`", 0, 1),
+    (2, "1", 1, 1),
+    (3, "2", 2, 1),
+    (4, "3", 3, 1),
+    (5, "4", 4, 1),
+    (6, "5", 5, 1),
+    (7, "6", 6, 1),
+    (8, "7", 7, 1),
+    (9, "8", 8, 1),
+    (10, "9", 9, 1),
+    (11, "0", 10, 1),
+    (12, "-", 11, 1),
+    (13, "=", 12, 1),
+    (14, "Backspace", 13, 1),
+    (110, "Insert", 15, 1),
+    (102, "Home", 16, 1),
+    (104, "Page Up", 17, 1),
+    (71, "Number Pad 7", 18, 1),
+    (72, "Number Pad 8", 19, 1),
+    (73, "Number Pad 9", 20, 1),
+    (78, "Number Pad +", 21, 1),
+    // Row 2
+    (15, "Tab", 0, 2),
+    (16, "Q", 1, 2),
+    (17, "W", 2, 2),
+    (18, "E", 3, 2),
+    (19, "R", 4, 2),
+    (20, "T", 5, 2),
+    (21, "Y", 6, 2),
+    (22, "U", 7, 2),
+    (23, "I", 8, 2),
+    (24, "O", 9, 2),
+    (25, "P", 10, 2),
+    (26, "[", 11, 2),
+    (27, "]", 12, 2),
+    (43, "\\ (ANSI)", 13, 2),
+    (111, "Delete", 15, 2),
+    (107, "End", 16, 2),
+    (109, "Page Down", 17, 2),
+    (75, "Number Pad 4", 18, 2),
+    (76, "Number Pad 5", 19, 2),
+    (77, "Number Pad 6", 20, 2),
+    // Row 3
+    (58, "Caps Lock", 0, 3),
+    (30, "A", 1, 3),
+    (31, "S", 2, 3),
+    (32, "D", 3, 3),
+    (33, "F", 4, 3),
+    (34, "G", 5, 3),
+    (35, "H", 6, 3),
+    (36, "J", 7, 3),
+    (37, "K", 8, 3),
+    (38, "L", 9, 3),
+    (39, ";", 10, 3),
+    (40, "'", 11, 3),
+    (28, "Enter", 12, 3),
+    (79, "Number Pad 1", 18, 3),
+    (80, "Number Pad 2", 19, 3),
+    (81, "Number Pad 3", 20, 3),
+    (96, "Number Pad Enter", 21, 3),
+    // Row 4
+    (42, "Left Shift", 0, 4),
+    (44, "Z", 1, 4),
+    (45, "X", 2, 4),
+    (46, "C", 3, 4),
+    (47, "V", 4, 4),
+    (48, "B", 5, 4),
+    (49, "N", 6, 4),
+    (50, "M", 7, 4),
+    (51, ",", 8, 4),
+    (52, ".", 9, 4),
+    (53, "/", 10, 4),
+    (54, "Right Shift", 12, 4),
+    (103, "Up Arrow", 19, 4),
+    (82, "Number Pad 0", 21, 4),
+    // Row 5
+    (29, "Left Control", 0, 5),
+    (125, "Left Windows", 1, 5),
+    (56, "Left Alt", 2, 5),
+    (57, "Space", 10, 5),
+    (100, "Right Alt", 12, 5),
+    (127, "Menu", 13, 5),
+    (97, "Right Control", 14, 5),
+    (105, "Left Arrow", 18, 5),
+    (108, "Down Arrow", 19, 5),
+    (106, "Right Arrow", 20, 5),
+    (83, "Number Pad .", 21, 5),
+];
+
+pub fn key_position(code: u16) -> Option<(i32, i32)> {
+    KEYS.iter()
+        .find(|(c, ..)| *c == code)
+        .map(|&(_, _, x, y)| (x, y))
+}
+
+// Key code for a name as written in the config: the OpenRGB key names above
+// ("Up Arrow", "A", "Escape") or the short forms up/down/left/right/esc.
+pub fn key_code(name: &str) -> Option<u16> {
+    let name = match name.to_lowercase().as_str() {
+        "up" => "Up Arrow",
+        "down" => "Down Arrow",
+        "left" => "Left Arrow",
+        "right" => "Right Arrow",
+        "esc" => "Escape",
+        _ => name,
+    };
+    KEYS.iter()
+        .find(|(_, n, ..)| n.eq_ignore_ascii_case(name))
+        .map(|&(c, ..)| c)
+}
+
+fn named_position(led_name: &str) -> Option<(i32, i32)> {
+    let name = led_name.strip_prefix("Key: ")?;
+    KEYS.iter()
+        .find(|(_, n, ..)| n.eq_ignore_ascii_case(name))
+        .map(|&(_, _, x, y)| (x, y))
+}
+
+// Grid cell for every LED of a keyboard, in the controller's LED order.
+pub struct KeyLayout {
+    cells: Vec<Option<(i32, i32)>>,
+}
+
+impl KeyLayout {
+    // None when too few LED names are recognized to trust them; the caller
+    // then falls back to writing the grid row by row.
+    pub fn from_controller(c: &Controller) -> Option<Self> {
+        let cells: Vec<_> = c.led_iter().map(|led| named_position(led.name())).collect();
+        let known = cells.iter().filter(|p| p.is_some()).count();
+        if known * 2 < cells.len() || known == 0 {
+            return None;
+        }
+        Some(KeyLayout { cells })
+    }
+
+    // Appends each key's color, picked out of a row-major `w`x`h` frame.
+    // Keys outside the grid or without a known position stay dark.
+    pub fn sample_into(&self, frame: &[Color], w: usize, h: usize, out: &mut Vec<Color>) {
+        out.extend(self.cells.iter().map(|cell| match *cell {
+            Some((x, y)) if (x as usize) < w && (y as usize) < h => {
+                frame[y as usize * w + x as usize]
+            }
+            _ => Color::new(0, 0, 0),
+        }));
+    }
+}
@@ -14,33 +14,94 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use openrgb2::{Color, Controller, OpenRgbClient};
-use rand::Rng;
-use std::collections::VecDeque;
+mod audio;
+mod canvas;
+mod color;
+mod config;
+mod control;
+mod device;
+mod effect;
+mod font;
+mod http;
+mod layout;
+mod metrics;
+mod minesweeper;
+mod mpris;
+mod preview;
+mod screen;
+mod snake;
+mod snapshot;
+mod sysmon;
+mod tetris;
+
+use canvas::Canvas;
+use clap::Parser;
+use config::{Cli, Config, GRID_HEIGHT, GRID_WIDTH, INPUT_DEVICE_PATH, OnExit, TICK_RATE_MS};
+use device::{Device, MockController};
+use effect::{
+    Ambient, Audio, Blend, Breathe, Clock, Effect, GameOver, Heatmap, Life, Matrix, Metronome,
+    Pomodoro, PomodoroTimer, Rainbow, Screen, Shared, Solid, Sunrise, Text, Water,
+};
+use futures::future::join_all;
+use layout::KeyLayout;
+use log::{debug, error, info, warn};
+use metrics::Metrics;
+use minesweeper::Minesweeper;
+use openrgb2::{Color, Controller, ControllerModeKind, DeviceType, OpenRgbClient, OpenRgbResult};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use snake::Snake;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fs::File;
 use std::io::Read;
-use std::mem::MaybeUninit;
-use std::slice;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use sysmon::SystemLoad;
+use tetris::Tetris;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 use tokio::time::interval;
 
-// --- CONFIGURATION ---
-const GRID_WIDTH: usize = 22;
-const GRID_HEIGHT: usize = 6;
-const TICK_RATE_MS: u64 = 30;
-const INPUT_DEVICE_PATH: &str = "/dev/input/event9";
+const OPENRGB_PROTOCOL: u32 = 5;
+const CONNECT_ATTEMPTS: u32 = 5;
+// Frames in a row where every write failed before assuming the server is gone.
+const RECONNECT_AFTER_FAILED_FRAMES: u32 = 10;
+const RECONNECT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+const INPUT_MAX_BACKOFF: Duration = Duration::from_secs(10);
+// Consecutive write errors before a single device is given up on.
+const DEVICE_FAIL_AFTER_ERRORS: u32 = 50;
+const ERROR_LOG_INTERVAL: Duration = Duration::from_secs(5);
 
 // --- KEY CODES ---
 const EV_KEY: u16 = 1;
+const KEY_ESC: u16 = 1;
 const KEY_W: u16 = 17;
+const KEY_R: u16 = 19;
 const KEY_A: u16 = 30;
 const KEY_S: u16 = 31;
 const KEY_D: u16 = 32;
+const KEY_F: u16 = 33;
+const KEY_SPACE: u16 = 57;
 const KEY_UP: u16 = 103;
 const KEY_DOWN: u16 = 108;
 const KEY_LEFT: u16 = 105;
 const KEY_RIGHT: u16 = 106;
+const KEY_CAPSLOCK: u16 = 58;
+const KEY_NUMLOCK: u16 = 69;
+const EV_LED: u16 = 0x11;
+const LED_NUML: u16 = 0;
+const LED_CAPSL: u16 = 1;
+const EV_ABS: u16 = 3;
+const ABS_X: u16 = 0;
+const ABS_Y: u16 = 1;
+const ABS_HAT0X: u16 = 0x10;
+const ABS_HAT0Y: u16 = 0x11;
+const BTN_DPAD_UP: u16 = 0x220;
+const BTN_DPAD_DOWN: u16 = 0x221;
+const BTN_DPAD_LEFT: u16 = 0x222;
+const BTN_DPAD_RIGHT: u16 = 0x223;
 
 // --- RAW INPUT STRUCTS ---
 #[derive(Debug)]
@@ -53,35 +114,252 @@ struct InputEvent {
     value: i32,
 }
 
+const INPUT_EVENT_SIZE: usize = std::mem::size_of::<InputEvent>();
+
 impl InputEvent {
-    fn new_uninit() -> MaybeUninit<Self> {
-        MaybeUninit::uninit()
+    // Decodes a `struct input_event` as laid out by the kernel (native endian).
+    fn parse(buf: &[u8; INPUT_EVENT_SIZE]) -> Self {
+        let field = |at: usize, n: usize| &buf[at..at + n];
+        InputEvent {
+            time_sec: i64::from_ne_bytes(field(0, 8).try_into().unwrap()),
+            time_usec: i64::from_ne_bytes(field(8, 8).try_into().unwrap()),
+            type_: u16::from_ne_bytes(field(16, 2).try_into().unwrap()),
+            code: u16::from_ne_bytes(field(18, 2).try_into().unwrap()),
+            value: i32::from_ne_bytes(field(20, 4).try_into().unwrap()),
+        }
+    }
+
+    fn read_from(f: &mut impl Read) -> std::io::Result<Self> {
+        let mut buf = [0u8; INPUT_EVENT_SIZE];
+        f.read_exact(&mut buf)?;
+        Ok(Self::parse(&buf))
+    }
+}
+
+// What the input tasks send to the render loop.
+enum Input {
+    Key(u16, KeyState),
+    Led(u16, bool), // a lock LED, e.g. LED_CAPSL, went on or off
+}
+
+// Forwards events from one input device until reading fails, or returns Ok
+// once nobody is listening. `read_exact` completes short reads itself, so an
+// error here means the stream is broken or out of step; reopening the device
+// starts again on an event boundary.
+fn read_input(path: &str, tx: &mpsc::UnboundedSender<Input>) -> std::io::Result<()> {
+    let mut f = File::open(path)?;
+    // Lock LEDs only report changes, so start from their current state.
+    if let Ok(leds) = evdev::Device::open(path).and_then(|d| d.get_led_state()) {
+        for (led, code) in [
+            (LED_CAPSL, evdev::LedCode::LED_CAPSL),
+            (LED_NUML, evdev::LedCode::LED_NUML),
+        ] {
+            let _ = tx.send(Input::Led(led, leds.contains(code)));
+        }
+    }
+    info!("Reading input from {}", path);
+
+    loop {
+        let event = InputEvent::read_from(&mut f)?;
+        let input = match event.type_ {
+            EV_KEY => match KeyState::from_value(event.value) {
+                Some(key) => Input::Key(event.code, key),
+                None => continue,
+            },
+            EV_LED => Input::Led(event.code, event.value != 0),
+            _ => continue,
+        };
+        if tx.send(input).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+// Turns gamepad events into arrow key presses. D-pads show up either as a
+// hat axis or as buttons; the stick counts once it is past half way.
+struct Gamepad {
+    stick: [(i32, i32); 2], // ABS_X and ABS_Y (min, max)
+    held: [i32; 4],         // -1, 0 or 1 for ABS_X, ABS_Y, ABS_HAT0X, ABS_HAT0Y
+}
+
+impl Gamepad {
+    fn new(stick: [(i32, i32); 2]) -> Self {
+        Gamepad {
+            stick,
+            held: [0; 4],
+        }
+    }
+
+    fn translate(&mut self, event: &InputEvent, out: &mut Vec<Input>) {
+        let (axis, dir) = match (event.type_, event.code) {
+            (EV_KEY, BTN_DPAD_UP | BTN_DPAD_DOWN | BTN_DPAD_LEFT | BTN_DPAD_RIGHT) => {
+                let key =
+                    [KEY_UP, KEY_DOWN, KEY_LEFT, KEY_RIGHT][(event.code - BTN_DPAD_UP) as usize];
+                if let Some(state) = KeyState::from_value(event.value) {
+                    out.push(Input::Key(key, state));
+                }
+                return;
+            }
+            (EV_ABS, ABS_X | ABS_Y) => {
+                let (min, max) = self.stick[event.code as usize];
+                let quarter = (max - min) / 4;
+                let dir = if event.value < min + quarter {
+                    -1
+                } else if event.value > max - quarter {
+                    1
+                } else {
+                    0
+                };
+                (event.code as usize, dir)
+            }
+            (EV_ABS, ABS_HAT0X | ABS_HAT0Y) => {
+                ((event.code - ABS_HAT0X) as usize + 2, event.value.signum())
+            }
+            _ => return,
+        };
+        let was = std::mem::replace(&mut self.held[axis], dir);
+        if was == dir {
+            return;
+        }
+        let keys = if axis % 2 == 0 {
+            [KEY_LEFT, KEY_RIGHT]
+        } else {
+            [KEY_UP, KEY_DOWN]
+        };
+        let key = |d: i32| keys[(d + 1) as usize / 2];
+        if was != 0 {
+            out.push(Input::Key(key(was), KeyState::Released));
+        }
+        if dir != 0 {
+            out.push(Input::Key(key(dir), KeyState::Pressed));
+        }
+    }
+}
+
+// Like `read_input`, for a gamepad: directions arrive as arrow keys.
+fn read_gamepad(path: &str, tx: &mpsc::UnboundedSender<Input>) -> std::io::Result<()> {
+    let absinfo = evdev::Device::open(path)?.get_abs_state()?;
+    let range = |axis: u16| {
+        let info = &absinfo[axis as usize];
+        (info.minimum, info.maximum)
+    };
+    let mut gamepad = Gamepad::new([range(ABS_X), range(ABS_Y)]);
+    let mut f = File::open(path)?;
+    info!("Reading gamepad from {}", path);
+
+    let mut inputs = Vec::new();
+    loop {
+        let event = InputEvent::read_from(&mut f)?;
+        gamepad.translate(&event, &mut inputs);
+        for input in inputs.drain(..) {
+            if tx.send(input).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// `input_event.value` for EV_KEY events.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum KeyState {
+    Released,
+    Pressed,
+    Repeated, // auto-repeat while held
+}
+
+impl KeyState {
+    fn from_value(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(KeyState::Released),
+            1 => Some(KeyState::Pressed),
+            2 => Some(KeyState::Repeated),
+            _ => None,
+        }
     }
 }
 
 // --- UTILS ---
-fn key_to_grid(code: u16) -> (i32, i32) {
-    match code {
-        1 => (0, 0),                            // Esc
-        59..=68 => ((code - 59 + 2) as i32, 0), // F1-F10
-        41 => (0, 1),                           // Grave
-        2..=13 => ((code - 1) as i32, 1),       // 1 through =
-        15 => (0, 2),                           // Tab
-        16..=25 => ((code - 15) as i32, 2),     // Q through P
-        58 => (0, 3),                           // Caps
-        30..=38 => ((code - 29) as i32, 3),     // A through L
-        42 => (0, 4),                           // LShift
-        44..=50 => ((code - 43) as i32, 4),     // Z through M
-        29 => (0, 5),                           // LCtrl
-        103 => (19, 4),                         // Up
-        108 => (19, 5),                         // Down
-        105 => (18, 5),                         // Left
-        106 => (20, 5),                         // Right
-        57 => (10, 5),                          // Space
-        _ => (
-            rand::rng().random_range(0..GRID_WIDTH as i32),
-            rand::rng().random_range(0..GRID_HEIGHT as i32),
-        ),
+// Grid cell of a key, None for keys the layout doesn't know or that fall
+// outside a `width` x `height` grid.
+fn key_to_grid(code: u16, width: i32, height: i32) -> Option<(i32, i32)> {
+    layout::key_position(code).filter(|&(x, y)| (0..width).contains(&x) && (0..height).contains(&y))
+}
+
+fn lerp_color(a: Color, b: Color, t: f32) -> Color {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t) as u8;
+    Color::new(mix(a.r, b.r), mix(a.g, b.g), mix(a.b, b.b))
+}
+
+// --- POST PROCESSING ---
+fn scale_color(c: Color, k: f32) -> Color {
+    let scale = |v: u8| (v as f32 * k) as u8;
+    Color::new(scale(c.r), scale(c.g), scale(c.b))
+}
+
+// Per-channel gamma curve, built once so it costs a lookup per LED.
+struct GammaLut([u8; 256]);
+
+impl GammaLut {
+    fn new(gamma: f32) -> Self {
+        let mut table = [0; 256];
+        for (i, v) in table.iter_mut().enumerate() {
+            *v = ((i as f32 / 255.0).powf(gamma) * 255.0).round() as u8;
+        }
+        GammaLut(table)
+    }
+
+    fn apply(&self, c: Color) -> Color {
+        Color::new(
+            self.0[c.r as usize],
+            self.0[c.g as usize],
+            self.0[c.b as usize],
+        )
+    }
+}
+
+// Separable 3x3 box blur over the bright cells, added back onto the frame.
+fn apply_bloom(frame: &mut [Color], w: usize, h: usize, cfg: &config::BloomConfig) {
+    let bright: Vec<[f32; 3]> = frame
+        .iter()
+        .map(|c| {
+            if c.r.max(c.g).max(c.b) >= cfg.threshold {
+                [c.r as f32, c.g as f32, c.b as f32]
+            } else {
+                [0.0; 3]
+            }
+        })
+        .collect();
+
+    let blur = |src: &[[f32; 3]], horizontal: bool| -> Vec<[f32; 3]> {
+        let mut out = vec![[0.0; 3]; src.len()];
+        for y in 0..h {
+            for x in 0..w {
+                let mut acc = [0.0; 3];
+                for d in -1i32..=1 {
+                    let (sx, sy) = if horizontal {
+                        (x as i32 + d, y as i32)
+                    } else {
+                        (x as i32, y as i32 + d)
+                    };
+                    if sx < 0 || sy < 0 || sx >= w as i32 || sy >= h as i32 {
+                        continue;
+                    }
+                    let p = src[sy as usize * w + sx as usize];
+                    for ch in 0..3 {
+                        acc[ch] += p[ch] / 3.0;
+                    }
+                }
+                out[y * w + x] = acc;
+            }
+        }
+        out
+    };
+    let glow = blur(&blur(&bright, true), false);
+
+    for (c, g) in frame.iter_mut().zip(glow) {
+        c.r = c.r.saturating_add((g[0] * cfg.intensity) as u8);
+        c.g = c.g.saturating_add((g[1] * cfg.intensity) as u8);
+        c.b = c.b.saturating_add((g[2] * cfg.intensity) as u8);
     }
 }
 
@@ -91,296 +369,1098 @@ enum Mode {
     Ambient,
     Snake,
     GameOver,
+    Metronome,
+    Screen,
+    Tetris,
+    Minesweeper,
+    Pomodoro,
+    Life,
+    Heatmap,
+    Audio,
+    Rainbow,
+    Solid(Color),
+    Breathe(Color),
+    Matrix,
+    Text,
+    Clock,
+    Sunrise,
+    Blend {
+        a: AmbientEffect,
+        b: AmbientEffect,
+        mix: f32,
+    },
 }
 
-#[derive(Clone, Copy, PartialEq)]
-struct Point {
-    x: i32,
-    y: i32,
+impl Mode {
+    // Modes played with the arrow keys, where cheat codes are not matched.
+    fn is_game(&self) -> bool {
+        matches!(self, Mode::Snake | Mode::Tetris | Mode::Minesweeper)
+    }
+
+    // Sim tick for modes that look the same from one tick to the next; None
+    // runs at `tick_rate_ms`. Devices showing the water step at this rate too.
+    fn tick_ms(&self) -> Option<u64> {
+        match self {
+            Mode::Solid(_) => Some(1000),
+            Mode::Clock | Mode::Pomodoro => Some(250),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Mode::Ambient => "ambient",
+            Mode::Snake => "snake",
+            Mode::GameOver => "gameover",
+            Mode::Metronome => "metronome",
+            Mode::Screen => "screen",
+            Mode::Tetris => "tetris",
+            Mode::Minesweeper => "minesweeper",
+            Mode::Pomodoro => "pomodoro",
+            Mode::Life => "life",
+            Mode::Heatmap => "heatmap",
+            Mode::Audio => "audio",
+            Mode::Rainbow => "rainbow",
+            Mode::Solid(_) => "solid",
+            Mode::Breathe(_) => "breathe",
+            Mode::Matrix => "matrix",
+            Mode::Text => "text",
+            Mode::Clock => "clock",
+            Mode::Sunrise => "sunrise",
+            Mode::Blend { .. } => "blend",
+        }
+    }
+
+    // Modes a user can switch to by name. Solid, Breathe and Blend start from
+    // their defaults; callers with a config should use `SolidConfig::mode`,
+    // `BreatheConfig::mode` and `BlendConfig::mode` instead.
+    fn from_name(name: &str) -> Option<Mode> {
+        match name.to_lowercase().as_str() {
+            "ambient" => Some(Mode::Ambient),
+            "snake" => Some(Mode::Snake),
+            "metronome" => Some(Mode::Metronome),
+            "screen" => Some(Mode::Screen),
+            "tetris" => Some(Mode::Tetris),
+            "minesweeper" => Some(Mode::Minesweeper),
+            "pomodoro" => Some(Mode::Pomodoro),
+            "life" => Some(Mode::Life),
+            "heatmap" => Some(Mode::Heatmap),
+            "audio" => Some(Mode::Audio),
+            "rainbow" => Some(Mode::Rainbow),
+            "solid" => Some(config::SolidConfig::default().mode()),
+            "breathe" => Some(config::BreatheConfig::default().mode()),
+            "matrix" => Some(Mode::Matrix),
+            "text" => Some(Mode::Text),
+            "clock" => Some(Mode::Clock),
+            "sunrise" => Some(Mode::Sunrise),
+            "blend" => Some(config::BlendConfig::default().mode()),
+            _ => None,
+        }
+    }
+}
+
+// Arrow key sequences that switch mode, besides the configurable snake one.
+// Blend's settings come from the config.
+const CHEATS: [([u16; 6], Mode); 8] = [
+    (
+        [KEY_LEFT, KEY_RIGHT, KEY_LEFT, KEY_RIGHT, KEY_UP, KEY_UP],
+        Mode::Metronome,
+    ),
+    (
+        [KEY_DOWN, KEY_DOWN, KEY_UP, KEY_UP, KEY_LEFT, KEY_RIGHT],
+        Mode::Screen,
+    ),
+    (
+        [KEY_DOWN, KEY_DOWN, KEY_LEFT, KEY_LEFT, KEY_RIGHT, KEY_RIGHT],
+        Mode::Tetris,
+    ),
+    (
+        [KEY_UP, KEY_LEFT, KEY_DOWN, KEY_RIGHT, KEY_UP, KEY_LEFT],
+        Mode::Minesweeper,
+    ),
+    (
+        [KEY_RIGHT, KEY_RIGHT, KEY_LEFT, KEY_LEFT, KEY_UP, KEY_DOWN],
+        Mode::Life,
+    ),
+    (
+        [KEY_LEFT, KEY_DOWN, KEY_RIGHT, KEY_UP, KEY_LEFT, KEY_DOWN],
+        Mode::Heatmap,
+    ),
+    (
+        [KEY_UP, KEY_DOWN, KEY_UP, KEY_DOWN, KEY_LEFT, KEY_RIGHT],
+        Mode::Audio,
+    ),
+    (
+        [KEY_UP, KEY_UP, KEY_DOWN, KEY_DOWN, KEY_LEFT, KEY_LEFT],
+        Mode::Blend {
+            a: AmbientEffect::Water,
+            b: AmbientEffect::Rainbow,
+            mix: 0.5,
+        },
+    ),
+];
+
+// Stateless ambient effects that can be sampled on their own and mixed.
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum AmbientEffect {
+    Water,
+    Rainbow,
+}
+
+// Full-keyboard flashes drawn over whatever mode is running.
+struct Flash {
+    color: Color,
+    count: u32,
+    elapsed: f32, // seconds
+}
+
+impl Flash {
+    const PERIOD: f32 = 0.3; // one on/off cycle
+
+    fn lit(&self) -> bool {
+        self.elapsed % Flash::PERIOD < Flash::PERIOD / 2.0
+    }
+
+    fn finished(&self) -> bool {
+        self.elapsed >= Flash::PERIOD * self.count as f32
+    }
 }
 
-struct Ripple {
-    x: f32,
-    y: f32,
-    age: f32,
-    max_age: f32,
+// The mode being faded out, frozen where it was left.
+struct Transition {
+    effect: Box<dyn Effect>,
+    elapsed: f32, // seconds
 }
 
 struct AppState {
+    config: Arc<Config>,
     mode: Mode,
     width: i32,
     height: i32,
 
+    // Draws `mode` and takes its keys.
+    effect: Box<dyn Effect>,
+    transition: Option<Transition>,
+    flash: Option<Flash>,
+    // The water behind the mouse, RAM and the modes above. Effects carry their
+    // own copy, advanced in step with this one.
+    water: Water,
+
+    clock: f32,      // seconds of simulation
+    brightness: f32, // master multiplier, 0.0..=1.0
+    idle_dim: f32,   // on top of it, 1.0 until input has been idle a while
+    fade_in: f32,    // and the startup ramp, 0.0 to 1.0
+    tick_ms: u64,    // the sim interval in use, see `wanted_tick_ms`
+    rng: StdRng,     // seeded from `config.seed`; effects get their own from it
+
     input_history: VecDeque<u16>,
-    snake: Vec<Point>,
-    food: Point,
-    direction: Point,
-    snake_timer: u64,
-    last_snake_update: Instant,
-    ripples: Vec<Ripple>,
-    time_tick: f32,
-    game_over_timer: Option<Instant>,
+    heat: Shared<Vec<f32>>,         // per cell press counts, decaying
+    key_totals: BTreeMap<u16, u64>, // lifetime presses per keycode
+    high_score: usize,              // best snake score, persisted
+    text: String,                   // what text mode scrolls
+    last_key_cell: Option<(i32, i32)>,
+    last_input: Instant,
+    playlist_next: Instant, // when the playlist moves on
+    caps_lock: bool,
+    num_lock: bool,
+
+    screen: Shared<Vec<Color>>,    // columns from the capture task
+    audio_bands: Shared<Vec<f32>>, // 0.0..=1.0, low to high frequency
+
+    pomodoro: Shared<PomodoroTimer>,
 }
 
 impl AppState {
-    fn new(w: i32, h: i32) -> Self {
+    fn new(config: Arc<Config>) -> Self {
+        let w = config.grid_width as i32;
+        let h = config.grid_height as i32;
+        let water = Water::new(SystemLoad::default(), &config);
+        // Everything random draws from this, so a fixed seed replays a run.
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_rng(&mut rand::rng()),
+        };
         Self {
+            effect: Box::new(Ambient::new(config.clone(), water.clone())),
+            transition: None,
+            flash: None,
+            water,
+            heat: Shared::new(load_heatmap(&config, (w * h) as usize)),
+            key_totals: load_key_totals(&config),
+            clock: 0.0,
+            brightness: config.brightness.level,
+            text: config.text.message.clone(),
+            pomodoro: Shared::new(PomodoroTimer::new(&config)),
+            idle_dim: 1.0,
+            fade_in: if config.fade_in_secs > 0.0 { 0.0 } else { 1.0 },
+            tick_ms: config.tick_rate_ms,
+            playlist_next: Instant::now() + config.playlist.dwell(),
+            config,
             mode: Mode::Ambient,
             width: w,
             height: h,
             input_history: VecDeque::with_capacity(10),
-            snake: vec![],
-            food: Point { x: 0, y: 0 },
-            direction: Point { x: 1, y: 0 },
-            snake_timer: 150,
-            last_snake_update: Instant::now(),
-            ripples: Vec::new(),
-            time_tick: 0.0,
-            game_over_timer: None,
-        }
-    }
-
-    fn reset_snake(&mut self) {
-        self.snake = vec![
-            Point { x: 5, y: 3 },
-            Point { x: 4, y: 3 },
-            Point { x: 3, y: 3 },
-        ];
-        self.direction = Point { x: 1, y: 0 };
-        self.spawn_food();
-        self.mode = Mode::Snake;
-    }
-
-    fn spawn_food(&mut self) {
-        let mut rng = rand::rng();
-        loop {
-            let x = rng.random_range(0..self.width);
-            let y = rng.random_range(0..self.height);
-            let p = Point { x, y };
-            if !self.snake.contains(&p) {
-                self.food = p;
-                break;
+            high_score: 0,
+            last_key_cell: None,
+            last_input: Instant::now(),
+            caps_lock: false,
+            num_lock: false,
+            screen: Shared::default(),
+            audio_bands: Shared::default(),
+            rng,
+        }
+    }
+
+    fn enter_mode(&mut self, mode: Mode) {
+        if self.mode == Mode::Heatmap && mode != Mode::Heatmap {
+            self.save_heatmap();
+        }
+        self.effect.leave();
+        let config = self.config.clone();
+        let water = self.water.clone();
+        let effect: Box<dyn Effect> = match mode {
+            Mode::Ambient => Box::new(Ambient::new(config, water)),
+            Mode::Snake => {
+                self.high_score = load_high_score();
+                let rng = StdRng::from_rng(&mut self.rng);
+                Box::new(Snake::new(config, water, rng))
             }
+            Mode::GameOver => Box::new(self.game_over()),
+            Mode::Rainbow => Box::new(Rainbow::new(config)),
+            Mode::Solid(color) => Box::new(Solid::new(config, color)),
+            Mode::Breathe(color) => Box::new(Breathe::new(config, color)),
+            Mode::Text => Box::new(Text::new(config, self.text.clone())),
+            Mode::Clock => Box::new(Clock::new(config)),
+            Mode::Sunrise => Box::new(Sunrise::new(config)),
+            Mode::Metronome => Box::new(Metronome::new(config, water)),
+            Mode::Screen => Box::new(Screen::new(config, water, self.screen.clone())),
+            Mode::Blend { a, b, mix } => Box::new(Blend::new(config, water, a, b, mix)),
+            Mode::Tetris => Box::new(Tetris::new(
+                self.width,
+                self.height,
+                StdRng::from_rng(&mut self.rng),
+            )),
+            Mode::Minesweeper => Box::new(Minesweeper::new(
+                self.width,
+                self.height,
+                StdRng::from_rng(&mut self.rng),
+            )),
+            Mode::Heatmap => Box::new(Heatmap::new(config, self.heat.clone())),
+            Mode::Audio => Box::new(Audio::new(config, water, self.audio_bands.clone())),
+            Mode::Pomodoro => Box::new(Pomodoro::new(config, self.pomodoro.clone())),
+            Mode::Life => Box::new(Life::new(config, water, StdRng::from_rng(&mut self.rng))),
+            Mode::Matrix => Box::new(Matrix::new(config, StdRng::from_rng(&mut self.rng))),
+        };
+        let old = std::mem::replace(&mut self.effect, effect);
+        if self.config.transition_secs > 0.0 {
+            self.transition = Some(Transition {
+                effect: old,
+                elapsed: 0.0,
+            });
+        }
+        self.mode = mode;
+    }
+
+    // Scores the game that just ended, saving a new snake high score.
+    fn game_over(&mut self) -> GameOver {
+        let score = self.effect.score().unwrap_or(0);
+        // Two-player rounds don't count toward the solo best.
+        let solo = !self.config.snake.two_player;
+        let new_best = self.mode == Mode::Snake && solo && score > self.high_score;
+        if new_best {
+            self.high_score = score;
+            save_high_score(score);
+        }
+        let winner = self.effect.winner();
+        GameOver::new(self.config.clone(), score, new_best, winner)
+    }
+
+    // `Mode::from_name` with Solid, Breathe and Blend taken from the config.
+    fn mode_by_name(&self, name: &str) -> Option<Mode> {
+        Some(self.configured(Mode::from_name(name)?))
+    }
+
+    // `mode` with Solid, Breathe and Blend settings from the config.
+    fn configured(&self, mode: Mode) -> Mode {
+        match mode {
+            Mode::Solid(_) => self.config.solid.mode(),
+            Mode::Breathe(_) => self.config.breathe.mode(),
+            Mode::Blend { .. } => self.config.blend.mode(),
+            mode => mode,
         }
     }
 
-    fn handle_input(&mut self, code: u16) {
-        if self.input_history.len() >= 6 {
+    // Blinks the keyboard `count` times, replacing any flash still running.
+    fn flash(&mut self, color: Color, count: u32) {
+        self.flash = Some(Flash {
+            color,
+            count,
+            elapsed: 0.0,
+        });
+    }
+
+    fn set_brightness(&mut self, level: f32) {
+        self.brightness = level.clamp(0.0, 1.0);
+    }
+
+    // Where effects should react to a key. Unmapped keys land on a random
+    // cell so they still show something.
+    fn key_cell(&mut self, code: u16) -> (i32, i32) {
+        key_to_grid(code, self.width, self.height).unwrap_or_else(|| {
+            (
+                self.rng.random_range(0..self.width),
+                self.rng.random_range(0..self.height),
+            )
+        })
+    }
+
+    // Whether the most recent keys were `seq`.
+    fn typed(&self, seq: &[u16]) -> bool {
+        let len = self.input_history.len();
+        !seq.is_empty()
+            && len >= seq.len()
+            && self.input_history.iter().skip(len - seq.len()).eq(seq)
+    }
+
+    // Records `code` and switches mode if it completed a cheat sequence.
+    fn match_cheat(&mut self, code: u16) -> bool {
+        // Long enough for the longest cheat sequence.
+        if self.input_history.len() >= self.config.snake.cheat_codes.len().max(6) {
             self.input_history.pop_front();
         }
         self.input_history.push_back(code);
 
-        let seq = [KEY_UP, KEY_DOWN, KEY_LEFT, KEY_RIGHT, KEY_UP, KEY_DOWN];
-        if self.input_history.iter().eq(seq.iter()) {
-            println!(">>> CHEAT CODE: SNAKE MODE <<<");
-            self.reset_snake();
+        let mode = if self.typed(&self.config.snake.cheat_codes) {
+            Mode::Snake
+        } else {
+            match CHEATS.iter().find(|(seq, _)| self.typed(seq)) {
+                Some(&(_, mode)) => self.configured(mode),
+                None => return false,
+            }
+        };
+        debug!("Cheat code for {} mode", mode.name());
+        // Typed again, it goes back to ambient.
+        let next = if self.mode == mode {
+            Mode::Ambient
+        } else {
+            mode
+        };
+        self.enter_mode(next);
+        true
+    }
+
+    fn handle_input(&mut self, code: u16, key: KeyState) {
+        self.last_input = Instant::now();
+        self.idle_dim = 1.0;
+        match key {
+            KeyState::Pressed => {}
+            KeyState::Released => {
+                let (gx, gy) = self.key_cell(code);
+                self.effect.key_up(code, gx, gy);
+                return;
+            }
+            // Holding a key must not step snake or feed the cheat history.
+            KeyState::Repeated => return,
+        }
+        *self.key_totals.entry(code).or_default() += 1;
+
+        let b = &self.config.brightness;
+        if code == b.down_code || code == b.up_code {
+            let step = if code == b.up_code { b.step } else { -b.step };
+            self.set_brightness(self.brightness + step);
+            info!("Brightness {:.0}%", self.brightness * 100.0);
+            return;
+        }
+
+        // Only real key positions count towards the heatmap.
+        let cell = key_to_grid(code, self.width, self.height);
+        if let Some((x, y)) = cell {
+            self.last_key_cell = cell;
+            let i = (y * self.width + x) as usize;
+            self.heat.modify(|heat| heat[i] += 1.0);
+        }
+
+        // Games are steered with the arrows the cheats are made of.
+        if !self.mode.is_game() && self.match_cheat(code) {
             self.input_history.clear();
             return;
         }
 
-        match self.mode {
-            Mode::Ambient => {
-                let (gx, gy) = key_to_grid(code);
-                self.ripples.push(Ripple {
-                    x: gx as f32,
-                    y: gy as f32,
-                    age: 0.0,
-                    max_age: 12.0,
-                });
-            }
-            Mode::Snake => {
-                let new_dir = match code {
-                    KEY_UP | KEY_W if self.direction.y != 1 => Some(Point { x: 0, y: -1 }),
-                    KEY_DOWN | KEY_S if self.direction.y != -1 => Some(Point { x: 0, y: 1 }),
-                    KEY_LEFT | KEY_A if self.direction.x != 1 => Some(Point { x: -1, y: 0 }),
-                    KEY_RIGHT | KEY_D if self.direction.x != -1 => Some(Point { x: 1, y: 0 }),
-                    _ => None,
-                };
-                if let Some(d) = new_dir {
-                    self.direction = d;
-                }
-            }
-            Mode::GameOver => {}
+        let (gx, gy) = self.key_cell(code);
+        if code == KEY_ESC && self.effect.exits_on_esc() {
+            self.enter_mode(Mode::Ambient);
+        } else {
+            self.effect.key_down(code, gx, gy);
+        }
+    }
+
+    // Seconds per sim tick right now.
+    fn tick_secs(&self) -> f32 {
+        self.tick_ms as f32 / 1000.0
+    }
+
+    // Modes that barely change tick slower, unless something is fading or
+    // flashing over them.
+    fn wanted_tick_ms(&self) -> u64 {
+        let base = self.config.tick_rate_ms;
+        let dimming = self.idle_dim < 1.0 && self.idle_dim > self.config.idle_dim.level;
+        let animating =
+            self.transition.is_some() || self.flash.is_some() || self.fade_in < 1.0 || dimming;
+        match self.mode.tick_ms() {
+            Some(ms) if !animating => ms.max(base),
+            _ => base,
         }
     }
 
     fn update(&mut self) {
-        match self.mode {
-            Mode::Ambient => {
-                self.time_tick += 0.15;
-                for r in &mut self.ripples {
-                    r.age += 1.0;
-                }
-                self.ripples.retain(|r| r.age < r.max_age);
+        let dt = self.tick_secs();
+        let ticks = dt / self.config.tick_secs(); // in base ticks, for the water
+        self.clock += dt;
+        if let Some(t) = &mut self.transition {
+            t.elapsed += dt;
+            if t.elapsed >= self.config.transition_secs {
+                self.transition = None;
+            }
+        }
+        if let Some(f) = &mut self.flash {
+            f.elapsed += dt;
+            if f.finished() {
+                self.flash = None;
             }
+        }
+        if self.fade_in < 1.0 {
+            self.fade_in = (self.fade_in + dt / self.config.fade_in_secs).min(1.0);
+        }
+        let config = &self.config;
+        let switched = self
+            .pomodoro
+            .modify(|p| p.advance(dt, config).then(|| (p.working, p.color(config))));
+        if let Some((working, color)) = switched {
+            info!("Pomodoro: {}", if working { "work" } else { "break" });
+            self.flash(color, 3);
+        }
+        let half_life_ms = self.config.heatmap.half_life_mins.max(0.01) * 60_000.0;
+        let cooling = 0.5f32.powf(self.tick_ms as f32 / half_life_ms);
+        self.heat
+            .modify(|heat| heat.iter_mut().for_each(|h| *h *= cooling));
+
+        let dim = &self.config.idle_dim;
+        if let Some(secs) = dim.after_secs
+            && self.last_input.elapsed() >= Duration::from_secs(secs)
+        {
+            let step = dt / dim.fade_secs.max(0.01) * (1.0 - dim.level);
+            self.idle_dim = (self.idle_dim - step).max(dim.level);
+        }
+
+        let day_night = &self.config.day_night;
+        if day_night.enabled {
+            let (h, m, s) = effect::local_time();
+            self.water
+                .set_night_mix(day_night.night_mix(h * 3600 + m * 60 + s));
+        }
+
+        self.advance_playlist();
+
+        // A paused game waits however long it takes.
+        let paused = self.effect.paused();
+        let home = self.config.home_mode;
+        if self.mode != home
+            && !paused
+            && let Some(&secs) = self.config.idle_timeouts.get(self.mode.name())
+            && self.last_input.elapsed() >= Duration::from_secs(secs)
+        {
+            self.enter_mode(home);
+        }
+
+        match self.mode {
+            // Keeps the mouse and RAM in step with the effect's own water.
+            Mode::Ambient => self.water.advance(ticks),
             Mode::Snake => {
-                if self.last_snake_update.elapsed() >= Duration::from_millis(self.snake_timer) {
-                    self.step_snake();
-                    self.last_snake_update = Instant::now();
+                if self.config.snake.outside_ambient {
+                    self.water.advance(ticks);
                 }
             }
-            Mode::GameOver => {
-                if let Some(timer) = self.game_over_timer
-                    && timer.elapsed() >= Duration::from_secs(5)
-                {
-                    self.mode = Mode::Ambient;
-                    self.game_over_timer = None;
-                }
+            Mode::GameOver | Mode::Tetris | Mode::Minesweeper | Mode::Pomodoro => {}
+            Mode::Metronome
+            | Mode::Screen
+            | Mode::Heatmap
+            | Mode::Audio
+            | Mode::Rainbow
+            | Mode::Solid(_)
+            | Mode::Breathe(_)
+            | Mode::Matrix
+            | Mode::Life
+            | Mode::Text
+            | Mode::Clock
+            | Mode::Sunrise
+            | Mode::Blend { .. } => {
+                self.water.advance(ticks);
             }
         }
+
+        self.effect.update(dt);
+        if let Some(next) = self.effect.next_mode() {
+            self.enter_mode(next);
+        }
+        self.tick_ms = self.wanted_tick_ms();
     }
 
-    fn step_snake(&mut self) {
-        let head = self.snake[0];
-        let new_head = Point {
-            x: head.x + self.direction.x,
-            y: head.y + self.direction.y,
+    fn save_heatmap(&self) {
+        if !self.config.heatmap.persist {
+            return;
+        }
+        let Some(path) = config::state_dir().map(|d| d.join("heatmap")) else {
+            return;
         };
+        let text: Vec<String> = self.heat.get().iter().map(|h| h.to_string()).collect();
+        let written = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&path, text.join(" ")));
+        if let Err(e) = written {
+            warn!("Could not save heatmap to {}: {}", path.display(), e);
+        }
+    }
 
-        if new_head.x < 0
-            || new_head.x >= self.width
-            || new_head.y < 0
-            || new_head.y >= self.height
-            || self.snake.contains(&new_head)
-        {
-            self.mode = Mode::GameOver;
-            self.game_over_timer = Some(Instant::now());
+    // {"30":120,"57":88,...}, keyed by evdev code.
+    fn key_totals_json(&self) -> String {
+        let entries: Vec<String> = self
+            .key_totals
+            .iter()
+            .map(|(code, n)| format!("\"{}\":{}", code, n))
+            .collect();
+        format!("{{{}}}", entries.join(","))
+    }
+
+    fn save_key_totals(&self) {
+        if !self.config.heatmap.persist_totals {
+            return;
+        }
+        let Some(path) = config::state_dir().map(|d| d.join("key_totals")) else {
             return;
+        };
+        let text: String = self
+            .key_totals
+            .iter()
+            .map(|(code, n)| format!("{} {}\n", code, n))
+            .collect();
+        let written = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&path, text));
+        if let Err(e) = written {
+            warn!("Could not save key totals to {}: {}", path.display(), e);
         }
+    }
 
-        self.snake.insert(0, new_head);
-        if new_head == self.food {
-            self.spawn_food();
-            if self.snake_timer > 50 {
-                self.snake_timer -= 2;
+    fn get_keyboard_color(&self, x: i32, y: i32) -> Color {
+        let color = self.effect.color_at(x, y);
+        let color = match &self.transition {
+            Some(t) => {
+                let from = t.effect.color_at(x, y);
+                lerp_color(from, color, t.elapsed / self.config.transition_secs)
+            }
+            None => color,
+        };
+        match &self.flash {
+            Some(f) if f.lit() => f.color,
+            _ => color,
+        }
+    }
+
+    fn render_keyboard_frame(&self, grid: &mut Vec<Color>) {
+        grid.clear();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let mut color = self.get_keyboard_color(x, y);
+                if self.config.highlight.enabled {
+                    color = self.apply_highlight(x, y, color);
+                }
+                if self.config.lock_keys.enabled && self.lock_lit(x, y) {
+                    color = self.config.lock_keys.color;
+                }
+                grid.push(color);
             }
-        } else {
-            self.snake.pop();
         }
     }
 
-    fn get_water_base(&self, x: f32, y: f32) -> Color {
-        let t = self.time_tick;
-        let wave1 = ((x * 0.4) + (y * 0.4) + t).sin();
-        let wave2 = ((x * 0.6) - (t * 1.5)).cos();
-        let wave3 = ((y * 0.5) + (t * 0.5)).sin();
-        let combined = (wave1 + wave2 + wave3) / 3.0;
+    // Whether (x, y) is the Caps or Num Lock key with its lock on.
+    fn lock_lit(&self, x: i32, y: i32) -> bool {
+        [(KEY_CAPSLOCK, self.caps_lock), (KEY_NUMLOCK, self.num_lock)]
+            .iter()
+            .any(|&(code, on)| on && key_to_grid(code, self.width, self.height) == Some((x, y)))
+    }
+
+    fn set_lock(&mut self, led: u16, on: bool) {
+        match led {
+            LED_CAPSL => self.caps_lock = on,
+            LED_NUML => self.num_lock = on,
+            _ => {}
+        }
+    }
 
-        let brightness = 0.2 + (0.5 * combined);
+    fn apply_highlight(&self, x: i32, y: i32, base: Color) -> Color {
+        let Some((cx, cy)) = self.last_key_cell else {
+            return base;
+        };
+        let dx = (x - cx) as f32;
+        let dy = (y - cy) as f32;
+        let dist = (dx * dx + dy * dy).sqrt();
+        if dist >= 2.0 {
+            return base;
+        }
 
-        // Bluish Snow Palette
-        let r = (brightness * 200.0) as u8;
-        let g = (brightness * 220.0) as u8;
-        let b = (brightness * 255.0) as u8;
+        let mut strength = 1.0 - dist / 2.0;
+        if let Some(decay) = self.config.highlight.decay_secs {
+            strength *= (1.0 - self.last_input.elapsed().as_secs_f32() / decay).max(0.0);
+        }
 
-        Color::new(r, g, b)
+        lerp_color(base, self.config.highlight.color, strength)
     }
 
-    fn get_keyboard_color(&self, x: i32, y: i32) -> Color {
-        match self.mode {
-            Mode::Ambient => {
-                let mut base = self.get_water_base(x as f32, y as f32);
-
-                for r in &self.ripples {
-                    let rdx = x as f32 - r.x;
-                    let rdy = y as f32 - r.y;
-                    let r_dist = (rdx * rdx + rdy * rdy).sqrt();
-                    let radius = r.age * 1.2;
-                    let width = 1.5;
-
-                    if (r_dist - radius).abs() < width {
-                        let fade = 1.0 - (r.age / r.max_age).powf(3.0);
-                        if fade > 0.0 {
-                            base.r = base.r.saturating_add((fade * 255.0) as u8);
-                            base.g = base.g.saturating_add((fade * 255.0) as u8);
-                            base.b = base.b.saturating_add((fade * 255.0) as u8);
-                        }
-                    }
-                }
-                base
+    fn advance_playlist(&mut self) {
+        let playlist = &self.config.playlist;
+        let modes = &playlist.mode_list;
+        if Instant::now() < self.playlist_next {
+            return;
+        }
+        let Some(at) = modes.iter().position(|&m| m == self.mode) else {
+            return;
+        };
+        let next = if playlist.shuffle && modes.len() > 1 {
+            // Anything but the current one.
+            (at + self.rng.random_range(1..modes.len())) % modes.len()
+        } else {
+            (at + 1) % modes.len()
+        };
+        self.playlist_next = Instant::now() + playlist.dwell();
+        info!("Playlist: {}", modes[next].name());
+        self.enter_mode(modes[next]);
+    }
+
+    // Called on mode changes made by hand.
+    fn pause_playlist(&mut self) {
+        let pause = Duration::from_secs_f32(self.config.playlist.pause_mins * 60.0);
+        self.playlist_next = self.playlist_next.max(Instant::now() + pause);
+    }
+
+    // Copies out what devices are drawn from. The keyboard frame is rendered
+    // here, into `grid`, since effects own their state.
+    fn snapshot(&self, mut grid: Vec<Color>) -> FrameSnapshot {
+        self.render_keyboard_frame(&mut grid);
+        FrameSnapshot {
+            config: self.config.clone(),
+            grid,
+            water: self.water.clone(),
+            clock: self.clock,
+            brightness: self.brightness * self.idle_dim * self.fade_in,
+        }
+    }
+}
+
+// Everything a frame is filled from, taken under a short lock so building
+// and sending device buffers never holds up input.
+struct FrameSnapshot {
+    config: Arc<Config>,
+    grid: Vec<Color>, // the keyboard frame, row-major
+    water: Water,
+    clock: f32,
+    brightness: f32, // including idle dimming
+}
+
+impl FrameSnapshot {
+    // Fills `leds` for a device not showing the scene. `index` is its place
+    // among the devices of its category.
+    fn device_colors(
+        &self,
+        effect: DeviceEffect,
+        index: usize,
+        count: usize,
+        leds: &mut Vec<Color>,
+    ) {
+        match effect {
+            // Drawn by the caller, which knows where the device sits.
+            DeviceEffect::Scene => {}
+            DeviceEffect::Water => leds.resize(count, self.water.color(10.0, 3.0)),
+            DeviceEffect::Ram => {
+                leds.extend((0..count).map(|i| self.get_ram_color(index, i, count)))
             }
-            Mode::Snake => {
-                let p = Point { x, y };
-                if self.snake.contains(&p) {
-                    if self.snake[0] == p {
-                        return Color::new(0, 255, 0);
-                    }
-                    return Color::new(0, 150, 0);
-                }
-                if self.food == p {
-                    return Color::new(255, 0, 255);
-                }
-                Color::new(5, 5, 5)
-            }
-            Mode::GameOver => {
-                let elapsed_ms = self
-                    .game_over_timer
-                    .map(|t| t.elapsed().as_millis())
-                    .unwrap_or(0);
-                if (elapsed_ms / 250).is_multiple_of(2) {
-                    Color::new(255, 0, 0)
-                } else {
-                    Color::new(0, 0, 0)
-                }
+            DeviceEffect::Rainbow => {
+                let hue = Rainbow::hue(&self.config, self.clock);
+                leds.extend(
+                    (0..count).map(|i| Rainbow::color(&self.config, hue, i as f32 / count as f32)),
+                )
+            }
+            DeviceEffect::Gradient => {
+                leds.extend((0..count).map(|i| self.get_strip_color(i, count)))
             }
+            DeviceEffect::Breathe => leds.resize(
+                count,
+                Breathe::color(&self.config, self.config.breathe.color, self.clock),
+            ),
+            DeviceEffect::Off => leds.resize(count, Color::new(0, 0, 0)),
+            DeviceEffect::Solid(color) => leds.resize(count, color),
+        }
+    }
+
+    fn get_strip_color(&self, led_idx: usize, count: usize) -> Color {
+        let cfg = &self.config.strip;
+        let along = (led_idx as f32 / count as f32 - self.clock * cfg.speed).rem_euclid(1.0);
+        let pos = along * cfg.colors.len() as f32;
+        let from = pos as usize % cfg.colors.len();
+        let to = (from + 1) % cfg.colors.len();
+        lerp_color(cfg.colors[from], cfg.colors[to], pos.fract())
+    }
+
+    // Fills a multi-zone mouse zone by zone, padding any LEDs the zones
+    // don't account for with black.
+    fn mouse_colors(&self, zones: &[MouseZone], count: usize, leds: &mut Vec<Color>) {
+        for (z, zone) in zones.iter().enumerate() {
+            leds.extend((0..zone.num_leds).map(|i| self.get_mouse_color(zones, z, i)));
+        }
+        leds.resize(count, Color::new(0, 0, 0));
+    }
+
+    // Breathing zones pulse the water color; the others hold it, strips
+    // taking the water a step further along for each LED.
+    fn get_mouse_color(&self, zones: &[MouseZone], zone_idx: usize, led_idx: usize) -> Color {
+        let base = self.water.color(10.0 + led_idx as f32, 3.0);
+        if !zones[zone_idx].breathe {
+            return base;
         }
+        let phase = self.clock / self.config.mouse.breathe_secs * std::f32::consts::TAU;
+        scale_color(base, 0.6 + 0.4 * phase.sin())
     }
 
     // UPDATED: High-Floor Brightness & Slower Animation
     fn get_ram_color(&self, stick_idx: usize, led_idx: usize, total_leds: usize) -> Color {
-        let x = stick_idx as f32;
+        let cfg = &self.config.ram;
+        let x = if cfg.sync { 0.0 } else { stick_idx as f32 };
+        let led_idx = if cfg.mirror && stick_idx % 2 == 1 {
+            total_leds - 1 - led_idx
+        } else {
+            led_idx
+        };
         let y_norm = led_idx as f32 / total_leds as f32;
-        let t = self.time_tick;
+        let t = self.water.time_tick;
 
         // Slow drift (0.4 factor)
         let phase = (x * 0.8) + (y_norm * 4.0) + (t * 0.4);
         let wave = phase.sin() * 0.5 + 0.5; // 0.0 to 1.0
 
         // COIL WHINE FIX:
-        // We enforce a minimum brightness floor of ~50.
-        // We oscillate between 50 and 200 (approx) instead of 0 and 50.
-
-        let min_b = 60.0;
-        let max_b = 200.0;
-        let min_g = 55.0;
-        let max_g = 180.0;
-        let min_r = 40.0;
-        let max_r = 110.0; // Keep R low to prevent pink tint
-
-        let r = (min_r + (wave * (max_r - min_r))) as u8;
-        let g = (min_g + (wave * (max_g - min_g))) as u8;
-        let b = (min_b + (wave * (max_b - min_b))) as u8;
+        // We enforce a minimum brightness floor (`ram.min`) and oscillate up
+        // to `ram.max` instead of dipping toward 0.
+        let channel = |i: usize| (cfg.min[i] + (wave * (cfg.max[i] - cfg.min[i]))) as u8;
+        let r = channel(0);
+        let g = channel(1);
+        let b = channel(2);
 
         Color::new(r, g, b)
     }
 }
 
-// --- DEVICE MANAGEMENT ---
-struct DeviceGroup {
-    keyboards: Vec<Controller>,
-    mice: Vec<Controller>,
-    rams: Vec<Controller>,
-    fans: Vec<Controller>,
+// --- HIGH SCORE ---
+fn high_score_path() -> Option<PathBuf> {
+    Some(config::state_dir()?.join("highscore"))
 }
 
-impl DeviceGroup {
-    fn sort<I>(controllers: I) -> Self
-    where
-        I: IntoIterator<Item = Controller>,
-    {
-        let mut group = DeviceGroup {
-            keyboards: vec![],
-            mice: vec![],
-            rams: vec![],
-            fans: vec![],
-        };
+fn load_high_score() -> usize {
+    high_score_path()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0)
+}
 
-        for c in controllers {
-            let name = c.name().to_lowercase();
-            if name.contains("keyboard") || name.contains("blackwidow") {
-                group.keyboards.push(c);
-            } else if name.contains("mouse") || name.contains("deathadder") {
-                group.mice.push(c);
+fn save_high_score(score: usize) {
+    let Some(path) = high_score_path() else {
+        return;
+    };
+    let written = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&path, format!("{}\n", score)));
+    if let Err(e) = written {
+        warn!("Could not save high score to {}: {}", path.display(), e);
+    }
+}
+
+// Stored counts when persistence is on and the grid size still matches.
+fn load_heatmap(cfg: &Config, cells: usize) -> Vec<f32> {
+    let stored = cfg
+        .heatmap
+        .persist
+        .then(config::state_dir)
+        .flatten()
+        .and_then(|d| std::fs::read_to_string(d.join("heatmap")).ok())
+        .and_then(|s| {
+            s.split_whitespace()
+                .map(|v| v.parse().ok())
+                .collect::<Option<Vec<f32>>>()
+        });
+    match stored {
+        Some(heat) if heat.len() == cells => heat,
+        _ => vec![0.0; cells],
+    }
+}
+
+// One "code count" pair per line; unreadable lines are skipped.
+fn load_key_totals(cfg: &Config) -> BTreeMap<u16, u64> {
+    let Some(text) = cfg
+        .heatmap
+        .persist_totals
+        .then(config::state_dir)
+        .flatten()
+        .and_then(|d| std::fs::read_to_string(d.join("key_totals")).ok())
+    else {
+        return BTreeMap::new();
+    };
+    text.lines()
+        .filter_map(|line| {
+            let (code, n) = line.split_once(' ')?;
+            Some((code.parse().ok()?, n.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+// --- DEVICE MANAGEMENT ---
+// Connects to `server` (or the local default), retrying with backoff.
+async fn connect(server: Option<&str>, attempts: u32) -> OpenRgbResult<OpenRgbClient> {
+    let mut delay = Duration::from_millis(500);
+    let mut attempt = 1;
+    loop {
+        let result = match server {
+            Some(addr) => OpenRgbClient::connect_to(addr, OPENRGB_PROTOCOL).await,
+            None => OpenRgbClient::connect().await,
+        };
+        match result {
+            Ok(client) => return Ok(client),
+            Err(e) if attempt < attempts => {
+                warn!(
+                    "Could not connect to OpenRGB at {} ({}), retrying in {:?}",
+                    server.unwrap_or("127.0.0.1:6742"),
+                    e,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => {
+                error!(
+                    "Could not connect to OpenRGB at {}",
+                    server.unwrap_or("127.0.0.1:6742")
+                );
+                return Err(e);
+            }
+        }
+    }
+}
+
+fn dedupe_controllers<I>(controllers: I, cfg: &Config) -> Vec<Controller>
+where
+    I: IntoIterator<Item = Controller>,
+{
+    let mut kept: Vec<Controller> = Vec::new();
+    for c in controllers {
+        let name = c.name().to_lowercase();
+        let keep = cfg
+            .keep_duplicate_controllers
+            .iter()
+            .any(|d| name.contains(&d.to_lowercase()));
+        let duplicate = !keep
+            && kept
+                .iter()
+                .any(|k| k.name() == c.name() && k.num_leds() == c.num_leds());
+        if duplicate {
+            info!(
+                "Merged duplicate controller '{}' ({} LEDs)",
+                c.name(),
+                c.num_leds()
+            );
+        } else {
+            kept.push(c);
+        }
+    }
+    kept
+}
+
+fn drop_empty_controllers(controllers: Vec<Controller>, cfg: &Config) -> Vec<Controller> {
+    if !cfg.skip_empty_controllers {
+        return controllers;
+    }
+    controllers
+        .into_iter()
+        .filter(|c| {
+            let usable = c.num_leds() > 0;
+            if !usable {
+                info!("Skipped '{}': no LEDs", c.name());
+            }
+            usable
+        })
+        .collect()
+}
+
+fn needs_commit(c: &impl Device, cfg: &Config) -> bool {
+    let name = c.name().to_lowercase();
+    cfg.commit_devices
+        .iter()
+        .any(|d| name.contains(&d.to_lowercase()))
+}
+
+async fn present(c: &impl Device, leds: &[Color], commit: bool) -> OpenRgbResult<()> {
+    c.set_leds(leds).await?;
+    if commit {
+        c.commit().await?;
+    }
+    Ok(())
+}
+
+async fn init_device(c: &impl Device) {
+    if let Err(e) = c.init().await {
+        warn!("Failed to init device '{}': {}", c.name(), e);
+    }
+}
+
+// Per-LED colors are silently dropped unless the controller runs a direct
+// mode, which some devices call "Custom" instead of "Direct".
+async fn enter_direct_mode(c: &Controller) {
+    let mode = c
+        .mode_iter()
+        .find(|m| m.kind() == ControllerModeKind::Direct)
+        .or_else(|| {
+            c.mode_iter()
+                .find(|m| m.name().eq_ignore_ascii_case("custom"))
+        });
+    match mode {
+        Some(mode) => {
+            if let Err(e) = mode.builder().execute(c).await {
+                warn!(
+                    "Could not put '{}' in {} mode: {}",
+                    c.name(),
+                    mode.name(),
+                    e
+                );
+            }
+        }
+        None => warn!(
+            "'{}' has no direct mode, it may keep its hardware effect",
+            c.name()
+        ),
+    }
+}
+
+// Connects, initializes every controller and sorts them into categories.
+async fn discover(cfg: &Config, attempts: u32) -> OpenRgbResult<DeviceGroup> {
+    let client = connect(cfg.server.as_deref(), attempts).await?;
+    let controllers = dedupe_controllers(client.get_all_controllers().await?, cfg);
+    let controllers = drop_empty_controllers(controllers, cfg);
+
+    info!("Initializing devices...");
+    for c in &controllers {
+        init_device(c).await;
+        enter_direct_mode(c).await;
+    }
+
+    let mut devices = DeviceGroup::sort(controllers, cfg);
+    devices.promote_mouse_fallback(cfg);
+    devices.keyboard_layouts = devices
+        .keyboards
+        .iter()
+        .map(KeyLayout::from_controller)
+        .collect();
+    devices.mouse_zones = devices
+        .mice
+        .iter()
+        .map(|c| MouseZone::from_controller(c, cfg))
+        .collect();
+
+    info!("Found Devices:");
+    info!("  Keyboards: {}", devices.keyboards.len());
+    for (kb, layout) in devices.keyboards.iter().zip(&devices.keyboard_layouts) {
+        if layout.is_none() {
+            info!(
+                "    '{}': no key names, using the row-major grid",
+                kb.name()
+            );
+        }
+    }
+    info!("  Mice:      {}", devices.mice.len());
+    info!("  RAM:       {}", devices.rams.len());
+    info!("  Fans/Misc: {}", devices.fans.len());
+    Ok(devices)
+}
+
+// Whether the OpenRGB connection is usable, or when to try it again.
+enum Link {
+    Healthy {
+        failed_frames: u32,
+    },
+    Reconnecting {
+        next_attempt: Instant,
+        backoff: Duration,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+enum Category {
+    #[serde(rename = "keyboards")]
+    Keyboard,
+    #[serde(rename = "mice")]
+    Mouse,
+    #[serde(rename = "rams")]
+    Ram,
+    #[serde(rename = "fans")]
+    Fan, // fans and everything else, kept dark
+}
+
+// What a device shows, set per category or device in `device_effects`.
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+#[serde(try_from = "String")]
+enum DeviceEffect {
+    Scene,    // the active mode: the grid on keyboards, the canvas elsewhere
+    Water,    // the water at one fixed spot
+    Ram,      // the slow high-floor gradient made for RAM sticks
+    Rainbow,  // hues along the device's LEDs
+    Gradient, // `strip` colors flowing along the LEDs
+    Breathe,  // `breathe.color` fading in and out
+    Off,
+    Solid(Color), // written as "#RRGGBB"
+}
+
+impl TryFrom<String> for DeviceEffect {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        Ok(match s.to_lowercase().as_str() {
+            "scene" => DeviceEffect::Scene,
+            "water" => DeviceEffect::Water,
+            "ram" => DeviceEffect::Ram,
+            "rainbow" => DeviceEffect::Rainbow,
+            "gradient" => DeviceEffect::Gradient,
+            "breathe" => DeviceEffect::Breathe,
+            "off" => DeviceEffect::Off,
+            _ => DeviceEffect::Solid(
+                config::parse_hex_color(&s)
+                    .ok_or_else(|| format!("unknown device effect \"{}\"", s))?,
+            ),
+        })
+    }
+}
+
+// Trusts the type OpenRGB reports; names are only consulted for Unknown.
+fn categorize(device_type: DeviceType, name: &str) -> Category {
+    match device_type {
+        DeviceType::Keyboard => Category::Keyboard,
+        DeviceType::Mouse => Category::Mouse,
+        DeviceType::DRam => Category::Ram,
+        DeviceType::Unknown => {
+            let name = name.to_lowercase();
+            if name.contains("keyboard") || name.contains("blackwidow") {
+                Category::Keyboard
+            } else if name.contains("mouse") || name.contains("deathadder") {
+                Category::Mouse
             } else if name.contains("dram")
                 || name.contains("memory")
                 || name.contains("ene")
@@ -388,132 +1468,1354 @@ impl DeviceGroup {
                 || name.contains("g.skill")
                 || name.contains("gigabyte")
             {
-                group.rams.push(c);
+                Category::Ram
             } else {
-                group.fans.push(c);
+                Category::Fan
             }
         }
-        group
+        _ => Category::Fan,
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("--- RGB DAEMON STARTED ---");
+// One zone of a mouse: its LEDs follow the previous zone's.
+struct MouseZone {
+    num_leds: usize,
+    breathe: bool, // named like one of `mouse.breathe`
+}
 
-    let client = OpenRgbClient::connect().await?;
-    let controllers = client.get_all_controllers().await?;
+impl MouseZone {
+    fn from_controller(c: &Controller, cfg: &Config) -> Vec<MouseZone> {
+        c.get_all_zones()
+            .map(|z| {
+                let name = z.name().to_lowercase();
+                MouseZone {
+                    num_leds: z.num_leds(),
+                    breathe: cfg
+                        .mouse
+                        .breathe
+                        .iter()
+                        .any(|b| name.contains(&b.to_lowercase())),
+                }
+            })
+            .collect()
+    }
+}
 
-    println!("Initializing devices...");
-    for c in &controllers {
-        if let Err(e) = c.init().await {
-            eprintln!("Warning: Failed to init device '{}': {}", c.name(), e);
+struct DeviceGroup<D = Controller> {
+    keyboards: Vec<D>,
+    keyboard_layouts: Vec<Option<KeyLayout>>, // parallel to `keyboards`
+    mice: Vec<D>,
+    mouse_zones: Vec<Vec<MouseZone>>, // parallel to `mice`
+    rams: Vec<D>,
+    fans: Vec<D>,
+}
+
+impl<D> Default for DeviceGroup<D> {
+    fn default() -> Self {
+        DeviceGroup {
+            keyboards: vec![],
+            keyboard_layouts: vec![],
+            mice: vec![],
+            mouse_zones: vec![],
+            rams: vec![],
+            fans: vec![],
         }
     }
+}
 
-    let devices = DeviceGroup::sort(controllers);
+// LED buffers kept per controller id and reused across frames. `next` is
+// rebuilt in place each frame; `shown` is what the controller last accepted.
+#[derive(Default)]
+struct LedBuffers {
+    next: Vec<Color>,
+    shown: Vec<Color>,
+    valid: bool,   // `shown` matches the device
+    pending: bool, // `next` was filled this frame
+    fresh: bool,   // `next` hasn't been through `finish` yet
+}
 
-    println!("Found Devices:");
-    println!("  Keyboards: {}", devices.keyboards.len());
-    println!("  Mice:      {}", devices.mice.len());
-    println!("  RAM:       {}", devices.rams.len());
-    println!("  Fans/Misc: {}", devices.fans.len());
+#[derive(Default)]
+struct FrameBuffers(HashMap<usize, LedBuffers>);
 
-    let app_state = Arc::new(Mutex::new(AppState::new(
-        GRID_WIDTH as i32,
-        GRID_HEIGHT as i32,
-    )));
+impl FrameBuffers {
+    // Empty buffer for `c`'s next frame.
+    fn next(&mut self, c: &impl Device) -> &mut Vec<Color> {
+        let b = self.0.entry(c.id()).or_default();
+        b.next.clear();
+        b.pending = true;
+        b.fresh = true;
+        &mut b.next
+    }
 
-    // --- INPUT TASK ---
-    let input_state = app_state.clone();
-    tokio::task::spawn_blocking(move || {
-        let mut f = match File::open(INPUT_DEVICE_PATH) {
-            Ok(f) => f,
-            Err(e) => {
-                eprintln!("CRITICAL: Could not open input: {}", e);
+    fn changed(&self, c: &impl Device) -> bool {
+        self.0
+            .get(&c.id())
+            .is_some_and(|b| b.pending && (!b.valid || b.next != b.shown))
+    }
+
+    fn frame(&self, c: &impl Device) -> &[Color] {
+        &self.0[&c.id()].next
+    }
+
+    // Runs the output stage over every buffer filled since the last call.
+    fn finish(&mut self, f: impl Fn(Color) -> Color) {
+        for b in self.0.values_mut().filter(|b| b.fresh) {
+            for c in &mut b.next {
+                *c = f(*c);
+            }
+            b.fresh = false;
+        }
+    }
+
+    fn accepted(&mut self, c: &impl Device) {
+        if let Some(b) = self.0.get_mut(&c.id()) {
+            std::mem::swap(&mut b.next, &mut b.shown);
+            b.valid = true;
+            b.pending = false;
+        }
+    }
+
+    fn rejected(&mut self, c: &impl Device) {
+        if let Some(b) = self.0.get_mut(&c.id()) {
+            b.valid = false;
+            b.pending = false;
+        }
+    }
+
+    // A write `accepted` early failed after all; the next frame is resent.
+    fn invalidate(&mut self, c: &impl Device) {
+        if let Some(b) = self.0.get_mut(&c.id()) {
+            b.valid = false;
+        }
+    }
+}
+
+// Writes still running, by controller id. Each has its own task and is never
+// cancelled: dropped halfway, it would leave part of a packet on the shared
+// OpenRGB connection. A controller is skipped until its write is done.
+#[derive(Default)]
+struct Writes(HashMap<usize, JoinHandle<OpenRgbResult<()>>>);
+
+impl Writes {
+    fn busy(&self, c: &impl Device) -> bool {
+        self.0.contains_key(&c.id())
+    }
+
+    fn start<D: Device>(
+        &mut self,
+        devices: &Arc<DeviceGroup<D>>,
+        c: &D,
+        leds: &[Color],
+        commit: bool,
+    ) {
+        let (devices, id, leds) = (devices.clone(), c.id(), leds.to_vec());
+        let write = tokio::spawn(async move {
+            let c = devices.all().find(|c| c.id() == id).expect("known device");
+            present(c, &leds, commit).await
+        });
+        self.0.insert(id, write);
+    }
+
+    // How `c`'s write went, if it is done by `deadline`.
+    async fn wait(
+        &mut self,
+        c: &impl Device,
+        deadline: tokio::time::Instant,
+    ) -> Option<OpenRgbResult<()>> {
+        let write = self.0.get_mut(&c.id())?;
+        let joined = tokio::time::timeout_at(deadline, write).await.ok()?;
+        self.0.remove(&c.id());
+        Some(joined.expect("device write panicked"))
+    }
+}
+
+// Write errors per controller id. Logging is throttled so an unplugged
+// device doesn't flood the console every frame.
+#[derive(Default)]
+struct DeviceHealth {
+    errors: u32, // in a row
+    last_logged: Option<Instant>,
+    failed: bool,  // skipped until the next reconnect
+    timeouts: u32, // since the last timeout was logged
+    last_timeout_logged: Option<Instant>,
+}
+
+impl DeviceHealth {
+    fn record(&mut self, c: &impl Device, result: &OpenRgbResult<()>) {
+        let Err(e) = result else {
+            self.errors = 0;
+            return;
+        };
+        self.errors += 1;
+        if self
+            .last_logged
+            .is_none_or(|t| t.elapsed() >= ERROR_LOG_INTERVAL)
+        {
+            warn!(
+                "Failed to update '{}': {} ({} in a row)",
+                c.name(),
+                e,
+                self.errors
+            );
+            self.last_logged = Some(Instant::now());
+        }
+        if self.errors >= DEVICE_FAIL_AFTER_ERRORS {
+            warn!("'{}' keeps failing, disabling it until reconnect", c.name());
+            self.failed = true;
+        }
+    }
+
+    // The device didn't take its frame within `device_timeout_ms`.
+    fn timed_out(&mut self, c: &impl Device, timeout: Duration) {
+        self.timeouts += 1;
+        if self
+            .last_timeout_logged
+            .is_none_or(|t| t.elapsed() >= ERROR_LOG_INTERVAL)
+        {
+            warn!(
+                "'{}' is slower than {:?}, skipped {} frame(s)",
+                c.name(),
+                timeout,
+                self.timeouts
+            );
+            self.last_timeout_logged = Some(Instant::now());
+            self.timeouts = 0;
+        }
+    }
+}
+
+impl DeviceGroup {
+    fn sort<I>(controllers: I, cfg: &Config) -> Self
+    where
+        I: IntoIterator<Item = Controller>,
+    {
+        let mut group = DeviceGroup::default();
+
+        for c in controllers {
+            let forced = cfg.device_overrides.iter().find(|o| o.matches(c.name()));
+            if let Some(o) = forced {
+                info!("Override: '{}' -> {:?}", c.name(), o.category);
+            }
+            let category = match forced {
+                Some(o) => o.category,
+                None => categorize(c.device_type(), c.name()),
+            };
+            match category {
+                Category::Keyboard => group.keyboards.push(c),
+                Category::Mouse => group.mice.push(c),
+                Category::Ram => group.rams.push(c),
+                Category::Fan => group.fans.push(c),
+            }
+        }
+        group
+    }
+}
+
+impl<D: Device> DeviceGroup<D> {
+    fn all(&self) -> impl Iterator<Item = &D> {
+        self.keyboards
+            .iter()
+            .chain(&self.mice)
+            .chain(&self.rams)
+            .chain(&self.fans)
+    }
+
+    fn promote_mouse_fallback(&mut self, cfg: &Config) {
+        let Some(pattern) = &cfg.mouse_fallback else {
+            return;
+        };
+        if !self.mice.is_empty() {
+            return;
+        }
+        let pattern = pattern.to_lowercase();
+        if let Some(idx) = self
+            .fans
+            .iter()
+            .position(|c| c.name().to_lowercase().contains(&pattern))
+        {
+            let c = self.fans.remove(idx);
+            info!("No mouse found, using '{}' as the mouse accent", c.name());
+            self.mice.push(c);
+        }
+    }
+}
+
+// Prints every key event with its grid cell, for building keymaps.
+fn monitor_input(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let (width, height) = (config.grid_width as i32, config.grid_height as i32);
+    let mut readers = Vec::new();
+    for path in &config.input_devices {
+        let mut f = File::open(path)?;
+        println!("Monitoring {} (Ctrl-C to stop)", path);
+        let path = path.clone();
+        readers.push(std::thread::spawn(move || {
+            while let Ok(event) = InputEvent::read_from(&mut f) {
+                if event.type_ == EV_KEY {
+                    let grid = match key_to_grid(event.code, width, height) {
+                        Some((x, y)) => format!("({}, {})", x, y),
+                        None => "unmapped".to_string(),
+                    };
+                    println!(
+                        "{} code={:<4} value={} grid={}",
+                        path, event.code, event.value, grid
+                    );
+                }
+            }
+            warn!("Stopped reading {}", path);
+        }));
+    }
+    for reader in readers {
+        let _ = reader.join();
+    }
+    Ok(())
+}
+
+// Picks the first evdev node named like a keyboard that reports key events,
+// preferring ones with letter keys over e.g. "Keyboard Consumer Control".
+fn find_keyboard() -> Option<PathBuf> {
+    let mut candidates: Vec<(PathBuf, bool)> = evdev::enumerate()
+        .filter(|(_, dev)| {
+            dev.name()
+                .is_some_and(|n| n.to_lowercase().contains("keyboard"))
+                && dev.supported_events().contains(evdev::EventType::KEY)
+        })
+        .map(|(path, dev)| {
+            let letters = dev
+                .supported_keys()
+                .is_some_and(|k| k.contains(evdev::KeyCode::KEY_A));
+            (path, letters)
+        })
+        .collect();
+    candidates.sort();
+    candidates
+        .iter()
+        .find(|(_, letters)| *letters)
+        .or(candidates.first())
+        .map(|(path, _)| path.clone())
+}
+
+// Blocks until a key goes down on one of `paths`, returning that device.
+fn detect_keyboard(paths: Vec<PathBuf>) -> Option<PathBuf> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    for path in paths {
+        let tx = tx.clone();
+        std::thread::spawn(move || {
+            let Ok(mut f) = File::open(&path) else {
                 return;
+            };
+            while let Ok(event) = InputEvent::read_from(&mut f) {
+                if event.type_ == EV_KEY && event.value == 1 {
+                    let _ = tx.send(path);
+                    return;
+                }
+            }
+        });
+    }
+    drop(tx);
+    rx.recv().ok()
+}
+
+// Guided first run: find the input device, identify controllers, write a config.
+async fn setup(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    println!("--- RGB CTRL SETUP ---");
+
+    let mut keyboards = Vec::new();
+    println!("Input devices:");
+    for (path, dev) in evdev::enumerate() {
+        let name = dev.name().unwrap_or("<unnamed>");
+        println!("  {}  {}", path.display(), name);
+        if dev
+            .supported_keys()
+            .is_some_and(|k| k.contains(evdev::KeyCode::KEY_A))
+        {
+            keyboards.push(path);
+        }
+    }
+
+    println!("Press any key on the keyboard you want to use...");
+    let input = tokio::task::spawn_blocking(move || detect_keyboard(keyboards))
+        .await?
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|| INPUT_DEVICE_PATH.to_string());
+    println!("Using input device {}", input);
+
+    let client = connect(config.server.as_deref(), CONNECT_ATTEMPTS).await?;
+    let controllers = client.get_all_controllers().await?;
+    println!("OpenRGB controllers (each flashes white in turn):");
+    for (i, c) in controllers.iter().enumerate() {
+        println!("  [{}] {} ({} LEDs)", i, c.name(), c.num_leds());
+        if c.set_controllable_mode().await.is_err() {
+            continue;
+        }
+        for _ in 0..3 {
+            let _ = c.set_all_leds(Color::new(255, 255, 255)).await;
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            let _ = c.set_all_leds(Color::new(0, 0, 0)).await;
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+
+    let Some(path) = config::config_path() else {
+        eprintln!("Could not locate a config directory, set $HOME or $XDG_CONFIG_HOME");
+        return Ok(());
+    };
+    if path.exists() {
+        println!("{} already exists, leaving it untouched", path.display());
+        return Ok(());
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(
+        &path,
+        format!(
+            "# rgb-ctrl starter config (written by --setup)\n\
+             input_devices = [\"{}\"]\n\
+             grid_width = {}\n\
+             grid_height = {}\n\
+             tick_rate_ms = {}\n",
+            input, GRID_WIDTH, GRID_HEIGHT, TICK_RATE_MS
+        ),
+    )?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let mut config = match Config::load(&cli) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    // A detected keyboard is looked for again when it goes away, since it
+    // may come back under another event node.
+    let detected = config.input_devices.is_empty();
+    if detected && !cli.setup {
+        let path = match find_keyboard() {
+            Some(path) => path.display().to_string(),
+            None => {
+                warn!(
+                    "No keyboard detected, falling back to {}",
+                    INPUT_DEVICE_PATH
+                );
+                INPUT_DEVICE_PATH.to_string()
             }
         };
+        info!("Using input device {}", path);
+        config.input_devices = vec![path];
+    }
+    let config = Arc::new(config);
+
+    if cli.monitor_input {
+        return monitor_input(&config);
+    }
+    if cli.setup {
+        return setup(&config).await;
+    }
+
+    info!("--- RGB DAEMON STARTED ---");
+
+    // Without OpenRGB, mock devices stand in and the loop below still runs
+    // in full.
+    let devices = if cli.dry_run || cli.preview {
+        info!("Dry run, not connecting to OpenRGB");
+        None
+    } else {
+        Some(discover(&config, CONNECT_ATTEMPTS).await?)
+    };
+    let preview = cli.preview.then(preview::Preview::new);
 
+    let mut state = AppState::new(config.clone());
+    state.enter_mode(config.start_mode);
+    let app_state = Arc::new(Mutex::new(state));
+
+    // --- INPUT TASKS ---
+    // One reader per device; a failing device is reopened by its own task,
+    // backing off while it stays broken, e.g. unplugged. Keys are queued for
+    // the render loop, which owns all input handling.
+    let (input_tx, mut input_rx) = mpsc::unbounded_channel();
+    let inputs = config
+        .input_devices
+        .iter()
+        .map(|path| (path.clone(), false));
+    for (input_path, gamepad) in inputs.chain(config.gamepad.clone().map(|path| (path, true))) {
+        let input_tx = input_tx.clone();
+        let read = if gamepad { read_gamepad } else { read_input };
+        tokio::task::spawn_blocking(move || {
+            let mut input_path = input_path;
+            let mut backoff = Duration::from_secs(1);
+            loop {
+                let opened = Instant::now();
+                match read(&input_path, &input_tx) {
+                    Ok(()) => return, // the render loop is gone
+                    Err(e) => warn!(
+                        "Input {} failed ({}), reopening in {:?}",
+                        input_path, e, backoff
+                    ),
+                }
+                // A device that worked for a while starts over at one second.
+                if opened.elapsed() > INPUT_MAX_BACKOFF * 6 {
+                    backoff = Duration::from_secs(1);
+                }
+                std::thread::sleep(backoff);
+                backoff = (backoff * 2).min(INPUT_MAX_BACKOFF);
+                if detected
+                    && !gamepad
+                    && let Some(path) = find_keyboard()
+                {
+                    let path = path.display().to_string();
+                    if path != input_path {
+                        info!("Keyboard is now {}", path);
+                        input_path = path;
+                    }
+                }
+            }
+        });
+    }
+
+    // --- CONTROL SOCKET TASK ---
+    if let Some(path) = config.control_socket.clone() {
+        let control_state = app_state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = control::serve(&path, control_state).await {
+                error!("Control socket {} failed: {}", path.display(), e);
+            }
+        });
+    }
+
+    // --- SUNRISE TIMER TASK ---
+    // Sleeps until the next scheduled time, which is tomorrow's when today's
+    // has already passed.
+    if let Some(at) = config.sunrise.at_secs {
+        let sunrise_state = app_state.clone();
+        tokio::spawn(async move {
+            loop {
+                let (h, m, s) = effect::local_time();
+                let now = h * 3600 + m * 60 + s;
+                let wait = (at as i64 - now as i64).rem_euclid(86_400);
+                tokio::time::sleep(Duration::from_secs(wait.max(1) as u64)).await;
+                info!("Sunrise");
+                sunrise_state.lock().unwrap().enter_mode(Mode::Sunrise);
+                // Step past the scheduled second before looking again.
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }
+        });
+    }
+
+    // --- HTTP API TASK ---
+    if let Some(addr) = config.http.clone() {
+        let http_state = app_state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = http::serve(&addr, http_state).await {
+                error!("HTTP API on {} failed: {}", addr, e);
+            }
+        });
+    }
+
+    // --- METRICS TASK ---
+    let metrics = Arc::new(Metrics::default());
+    if let Some(addr) = config.metrics.clone() {
+        let (metrics, metrics_state) = (metrics.clone(), app_state.clone());
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(&addr, metrics, metrics_state).await {
+                error!("Metrics on {} failed: {}", addr, e);
+            }
+        });
+    }
+
+    // --- SCREEN CAPTURE TASK ---
+    let screen_state = app_state.clone();
+    let screen_config = config.clone();
+    tokio::task::spawn_blocking(move || {
+        let cfg = &screen_config.screen;
         loop {
-            let mut event_buffer = InputEvent::new_uninit();
-            unsafe {
-                let slice = slice::from_raw_parts_mut(
-                    event_buffer.as_mut_ptr() as *mut u8,
-                    std::mem::size_of::<InputEvent>(),
-                );
-                if f.read_exact(slice).is_err() {
-                    break;
+            std::thread::sleep(Duration::from_millis(cfg.capture_ms));
+            if screen_state.lock().unwrap().mode != Mode::Screen {
+                continue;
+            }
+            match screen::capture_columns(cfg.region, screen_config.grid_width) {
+                Some(columns) => screen_state.lock().unwrap().screen.set(columns),
+                None => {
+                    warn!("Screen capture unavailable, using ambient");
+                    let mut state = screen_state.lock().unwrap();
+                    state.enter_mode(Mode::Ambient);
                 }
             }
+        }
+    });
 
-            let event = unsafe { event_buffer.assume_init() };
-            if event.type_ == EV_KEY && event.value == 1 {
-                let mut state = input_state.lock().unwrap();
-                state.handle_input(event.code);
+    // --- AUDIO TASK ---
+    let audio_state = app_state.clone();
+    let audio_config = config.clone();
+    tokio::task::spawn_blocking(move || {
+        let cfg = &audio_config.audio;
+        loop {
+            std::thread::sleep(Duration::from_millis(250));
+            if audio_state.lock().unwrap().mode != Mode::Audio {
+                continue;
+            }
+            let result = audio::stream_bands(&cfg.source, cfg.max, |bands| {
+                let state = audio_state.lock().unwrap();
+                state.audio_bands.set(bands);
+                state.mode == Mode::Audio
+            });
+            if let Err(e) = result {
+                warn!(
+                    "Audio source {} unavailable ({}), using ambient",
+                    cfg.source, e
+                );
+                let mut state = audio_state.lock().unwrap();
+                state.audio_bands.set(Vec::new());
+                if state.mode == Mode::Audio {
+                    state.enter_mode(Mode::Ambient);
+                }
             }
         }
     });
 
+    // --- SYSTEM TINT TASK ---
+    if config.system_tint.enabled {
+        let system_load = app_state.lock().unwrap().water.system_load();
+        let tint_config = config.clone();
+        tokio::task::spawn_blocking(move || {
+            let cfg = &tint_config.system_tint;
+            let mut cpu = sysmon::CpuSampler::default();
+            let mut warned = false;
+            loop {
+                let load = match &cfg.temp_sensor {
+                    Some(path) => sysmon::read_temperature(cfg, path),
+                    None => cpu.sample(),
+                };
+                if load.is_none() && !warned && cfg.temp_sensor.is_some() {
+                    warn!("Could not read temperature sensor, keeping the static palette");
+                    warned = true;
+                }
+                system_load.set(load);
+                std::thread::sleep(Duration::from_millis(cfg.sample_ms));
+            }
+        });
+    }
+
+    // --- NOW PLAYING TASK ---
+    if config.mpris.enabled {
+        let now_playing = app_state.lock().unwrap().water.now_playing();
+        let interval = Duration::from_millis(config.mpris.poll_ms);
+        tokio::spawn(mpris::poll(now_playing, interval));
+    }
+
     // --- RENDER LOOP ---
-    let mut ticker = interval(Duration::from_millis(TICK_RATE_MS));
+    match devices {
+        Some(devices) => {
+            render_loop(
+                &config,
+                &app_state,
+                &mut input_rx,
+                devices,
+                preview,
+                &metrics,
+                async |attempts| discover(&config, attempts).await,
+            )
+            .await;
+        }
+        // Mocks always accept their frames, so there is never anything to
+        // reconnect.
+        None => {
+            let mocks = render_loop(
+                &config,
+                &app_state,
+                &mut input_rx,
+                mock_devices(&config),
+                preview,
+                &metrics,
+                async |_| Ok(mock_devices(&config)),
+            )
+            .await;
+            for c in mocks.all() {
+                info!("'{}' received {} frames", c.name(), c.frames_received());
+            }
+        }
+    }
+    Ok(())
+}
+
+// Stand-ins for a typical desk: a keyboard covering the grid, a mouse and
+// two RAM sticks.
+fn mock_devices(config: &Config) -> DeviceGroup<MockController> {
+    DeviceGroup {
+        keyboards: vec![MockController::new(
+            0,
+            "Mock Keyboard",
+            config.grid_width * config.grid_height,
+        )],
+        keyboard_layouts: vec![None],
+        mice: vec![MockController::new(1, "Mock Mouse", 2)],
+        mouse_zones: vec![vec![
+            MouseZone {
+                num_leds: 1,
+                breathe: true,
+            },
+            MouseZone {
+                num_leds: 1,
+                breathe: false,
+            },
+        ]],
+        rams: vec![
+            MockController::new(2, "Mock RAM 1", 8),
+            MockController::new(3, "Mock RAM 2", 8),
+        ],
+        fans: vec![],
+    }
+}
+
+// Fills the buffer of every device for this frame.
+fn fill_frame<D: Device>(
+    snap: &mut FrameSnapshot,
+    devices: &DeviceGroup<D>,
+    buffers: &mut FrameBuffers,
+    tick_count: u64,
+) {
+    let config = &*snap.config;
+    if config.bloom.enabled {
+        apply_bloom(
+            &mut snap.grid,
+            config.grid_width,
+            config.grid_height,
+            &config.bloom,
+        );
+    }
+    let grid = &snap.grid;
+
+    let canvas = Canvas::new(grid, config.grid_width, config.grid_height, &snap.water);
+    let effects = &config.device_effects;
+    let due = |category| tick_count.is_multiple_of(config.update_every.get(category));
+
+    // 1. UPDATE KEYBOARDS
+    if due(Category::Keyboard) {
+        let keyboards = devices.keyboards.iter().zip(&devices.keyboard_layouts);
+        for (i, (kb, layout)) in keyboards.enumerate() {
+            let leds = buffers.next(kb);
+            if let Some(placement) = config.canvas.placement(kb.name()) {
+                canvas.sample_placed(placement, kb.num_leds(), leds);
+                continue;
+            }
+            match (effects.get(Category::Keyboard, kb.name()), layout) {
+                (DeviceEffect::Scene, Some(layout)) => {
+                    layout.sample_into(grid, config.grid_width, config.grid_height, leds)
+                }
+                (DeviceEffect::Scene, None) => {
+                    leds.extend_from_slice(grid);
+                    leds.resize(kb.num_leds(), Color::new(0, 0, 0));
+                }
+                (effect, _) => snap.device_colors(effect, i, kb.num_leds(), leds),
+            }
+        }
+    }
+
+    // 2. UPDATE RAM, MOUSE AND FANS
+    // Their scene is wherever they are placed on the canvas.
+    let placed = [
+        (Category::Ram, &devices.rams, config.canvas.rams),
+        (Category::Mouse, &devices.mice, config.canvas.mouse),
+        (Category::Fan, &devices.fans, config.canvas.fans),
+    ];
+    for (category, group, place) in placed {
+        if !due(category) {
+            continue;
+        }
+        for (i, c) in group.iter().enumerate() {
+            let leds = buffers.next(c);
+            if let Some(placement) = config.canvas.placement(c.name()) {
+                canvas.sample_placed(placement, c.num_leds(), leds);
+                continue;
+            }
+            let zones = match category {
+                Category::Mouse => devices.mouse_zones.get(i).map_or(&[][..], |z| z),
+                _ => &[],
+            };
+            match effects.get(category, c.name()) {
+                DeviceEffect::Scene => {
+                    canvas.sample_rect(&place.column(i, group.len()), c.num_leds(), leds)
+                }
+                DeviceEffect::Water if zones.len() > 1 => {
+                    snap.mouse_colors(zones, c.num_leds(), leds)
+                }
+                effect => snap.device_colors(effect, i, c.num_leds(), leds),
+            }
+        }
+    }
+}
+
+// Starts a write for every changed frame and waits for them until
+// `device_timeout_ms`, returning how many devices were tried and how many
+// writes went through. The writes share the one OpenRGB connection, whose
+// stream lock each takes in turn: they are pipelined back to back rather than
+// sent in parallel. One still running at the deadline is left to finish in
+// the background and its device skipped until it has, so a slow controller
+// only holds up its own frames.
+async fn send_frame<D: Device>(
+    devices: &Arc<DeviceGroup<D>>,
+    buffers: &mut FrameBuffers,
+    health: &mut HashMap<usize, DeviceHealth>,
+    writes: &mut Writes,
+    config: &Config,
+    metrics: &Metrics,
+) -> (usize, usize) {
+    let timeout = Duration::from_millis(config.device_timeout_ms);
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut accepted = 0;
+    let mut report = |c: &D,
+                      result: OpenRgbResult<()>,
+                      buffers: &mut FrameBuffers,
+                      health: &mut HashMap<usize, DeviceHealth>| {
+        health.entry(c.id()).or_default().record(c, &result);
+        if result.is_ok() {
+            accepted += 1;
+        } else {
+            metrics.device_error(c.name());
+            buffers.invalidate(c);
+        }
+    };
+
+    // Writes left behind by earlier frames, if they are done by now.
+    for c in devices.all() {
+        if let Some(result) = writes.wait(c, tokio::time::Instant::now()).await {
+            report(c, result, buffers, health);
+        }
+    }
+
+    let is_failed = |c: &D| health.get(&c.id()).is_some_and(|h| h.failed);
+    let failed = devices.all().filter(|c| is_failed(c)).count();
+    let changed: Vec<&D> = devices
+        .all()
+        .filter(|c| !is_failed(c) && buffers.changed(*c))
+        .collect();
+    let mut jobs = Vec::new();
+    for &c in &changed {
+        // Still on an earlier frame; this one is dropped and the next resent.
+        if writes.busy(c) {
+            health.entry(c.id()).or_default().timed_out(c, timeout);
+            buffers.rejected(c);
+            continue;
+        }
+        writes.start(devices, c, buffers.frame(c), needs_commit(c, config));
+        // Taken as shown, so the next frame is compared against it.
+        buffers.accepted(c);
+        jobs.push(c);
+    }
+    for c in jobs {
+        if let Some(result) = writes.wait(c, deadline).await {
+            report(c, result, buffers, health);
+        }
+    }
+
+    // Disabled devices count as failing, so losing all of them still
+    // triggers a reconnect.
+    (changed.len() + failed, accepted)
+}
+
+// Moves the sim ticker to `wanted` ms, starting a full period from now so
+// the next tick's step matches the time that passed.
+fn restart_ticker(ticker: &mut tokio::time::Interval, current: &mut u64, wanted: u64) {
+    if *current != wanted {
+        *current = wanted;
+        let period = Duration::from_millis(wanted);
+        *ticker = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    }
+}
+
+// Simulation and presentation run on separate timers so animation stays
+// smooth even when devices are written less often. `rediscover` brings a
+// lost connection back. Returns the devices once shut down.
+async fn render_loop<D: Device>(
+    config: &Config,
+    app_state: &Mutex<AppState>,
+    input: &mut mpsc::UnboundedReceiver<Input>,
+    devices: DeviceGroup<D>,
+    mut preview: Option<preview::Preview>,
+    metrics: &Metrics,
+    rediscover: impl AsyncFn(u32) -> OpenRgbResult<DeviceGroup<D>>,
+) -> Arc<DeviceGroup<D>> {
+    let mut devices = Arc::new(devices);
+    let mut sim_ms = config.tick_rate_ms;
+    let mut sim_ticker = interval(Duration::from_millis(sim_ms));
+    let mut present_ticker = interval(Duration::from_millis(config.present_rate_ms));
     let mut tick_count: u64 = 0;
+    let mut grid = Vec::new();
+    let mut buffers = FrameBuffers::default();
+    let gamma = GammaLut::new(config.gamma);
+    let mut health: HashMap<usize, DeviceHealth> = HashMap::new();
+    let mut writes = Writes::default();
+    let mut link = Link::Healthy { failed_frames: 0 };
+    let shutdown = tokio::signal::ctrl_c();
+    tokio::pin!(shutdown);
 
     loop {
-        ticker.tick().await;
+        tokio::select! {
+            _ = &mut shutdown => break,
+            // Input is handled as it arrives, so a slowly ticking mode still
+            // answers keys at once.
+            Some(input) = input.recv() => {
+                let mut state = app_state.lock().unwrap();
+                let mode = state.mode;
+                match input {
+                    Input::Key(code, key) => state.handle_input(code, key),
+                    Input::Led(led, on) => state.set_lock(led, on),
+                }
+                if state.mode != mode {
+                    state.pause_playlist();
+                }
+                state.tick_ms = state.wanted_tick_ms();
+                let wanted = state.tick_ms;
+                drop(state);
+                restart_ticker(&mut sim_ticker, &mut sim_ms, wanted);
+                continue;
+            }
+            _ = sim_ticker.tick() => {
+                let mut state = app_state.lock().unwrap();
+                state.update();
+                let wanted = state.tick_ms;
+                drop(state);
+                restart_ticker(&mut sim_ticker, &mut sim_ms, wanted);
+                continue;
+            }
+            _ = present_ticker.tick() => {}
+        }
         tick_count += 1;
 
+        if let Link::Reconnecting {
+            next_attempt,
+            backoff,
+        } = link
         {
-            let mut state = app_state.lock().unwrap();
-            state.update();
+            if Instant::now() < next_attempt {
+                continue;
+            }
+            match rediscover(1).await {
+                Ok(group) => {
+                    info!("Reconnected to OpenRGB");
+                    devices = Arc::new(group);
+                    buffers = FrameBuffers::default();
+                    health.clear();
+                    // Writes to the old connection finish on their own.
+                    writes = Writes::default();
+                    link = Link::Healthy { failed_frames: 0 };
+                }
+                Err(_) => {
+                    let backoff = (backoff * 2).min(RECONNECT_MAX_BACKOFF);
+                    link = Link::Reconnecting {
+                        next_attempt: Instant::now() + backoff,
+                        backoff,
+                    };
+                }
+            }
+            continue;
         }
 
-        let state = app_state.lock().unwrap();
+        // Only the keyboard frame is drawn under the lock; devices are filled
+        // and written from the snapshot after it is released. Every buffer is
+        // reused from the previous frame.
+        let started = Instant::now();
+        let mut snap = app_state
+            .lock()
+            .unwrap()
+            .snapshot(std::mem::take(&mut grid));
+        fill_frame(&mut snap, &devices, &mut buffers, tick_count);
+        let brightness = snap.brightness;
 
-        // 1. UPDATE KEYBOARDS
-        for kb in &devices.keyboards {
-            let mut leds = Vec::with_capacity(kb.num_leds());
-            for y in 0..GRID_HEIGHT {
-                for x in 0..GRID_WIDTH {
-                    leds.push(state.get_keyboard_color(x as i32, y as i32));
-                }
-            }
-            let target_len = kb.num_leds();
-            if leds.len() < target_len {
-                leds.resize(target_len, Color::new(0, 0, 0));
+        // Gamma is left out: it corrects for LEDs, not the terminal.
+        if let Some(preview) = &mut preview {
+            preview.draw(&snap.grid, config.grid_width, brightness);
+        }
+        grid = snap.grid;
+
+        // Output stage, applied once to everything filled this frame.
+        buffers.finish(|c| gamma.apply(scale_color(c, brightness)));
+
+        // Unchanged frames are not resent.
+        let (attempted, accepted) = send_frame(
+            &devices,
+            &mut buffers,
+            &mut health,
+            &mut writes,
+            config,
+            metrics,
+        )
+        .await;
+        metrics.frame(started.elapsed());
+
+        if let Link::Healthy { failed_frames } = &mut link {
+            if attempted > 0 && accepted == 0 {
+                *failed_frames += 1;
+            } else {
+                *failed_frames = 0;
             }
-            if leds.len() > target_len {
-                leds.truncate(target_len);
+            if *failed_frames >= RECONNECT_AFTER_FAILED_FRAMES {
+                warn!("Lost OpenRGB connection, reconnecting...");
+                link = Link::Reconnecting {
+                    next_attempt: Instant::now() + Duration::from_secs(1),
+                    backoff: Duration::from_secs(1),
+                };
             }
+        }
+    }
 
-            let _ = kb.set_leds(leds).await;
+    info!("Shutting down...");
+    {
+        let state = app_state.lock().unwrap();
+        state.save_heatmap();
+        state.save_key_totals();
+    }
+    if config.on_exit == OnExit::Black && matches!(link, Link::Healthy { .. }) {
+        let most = devices.all().map(|c| c.num_leds()).max().unwrap_or(0);
+        let black = vec![Color::new(0, 0, 0); most];
+        // Writes still running go first, so black is the last frame shown.
+        // A device that is still busy after that is left as it is.
+        let deadline =
+            tokio::time::Instant::now() + Duration::from_millis(config.device_timeout_ms);
+        for c in devices.all() {
+            writes.wait(c, deadline).await;
         }
+        join_all(
+            devices
+                .all()
+                .filter(|c| !writes.busy(*c))
+                .map(|c| present(c, &black[..c.num_leds()], needs_commit(c, config))),
+        )
+        .await;
+    }
+    devices
+}
 
-        // 2. UPDATE RAM (Throttled)
-        if tick_count.is_multiple_of(3) {
-            for (i, ram) in devices.rams.iter().enumerate() {
-                let count = ram.num_leds();
-                let mut leds = Vec::with_capacity(count);
-                for led_idx in 0..count {
-                    leds.push(state.get_ram_color(i, led_idx, count));
-                }
-                let _ = ram.set_leds(leds).await;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_input_event_bytes() {
+        let mut buf = [0u8; INPUT_EVENT_SIZE];
+        buf[0..8].copy_from_slice(&1_700_000_000i64.to_ne_bytes());
+        buf[8..16].copy_from_slice(&250_000i64.to_ne_bytes());
+        buf[16..18].copy_from_slice(&EV_KEY.to_ne_bytes());
+        buf[18..20].copy_from_slice(&KEY_W.to_ne_bytes());
+        buf[20..24].copy_from_slice(&2i32.to_ne_bytes());
+
+        let event = InputEvent::read_from(&mut &buf[..]).unwrap();
+        assert_eq!(event.time_sec, 1_700_000_000);
+        assert_eq!(event.time_usec, 250_000);
+        assert_eq!(event.type_, EV_KEY);
+        assert_eq!(event.code, KEY_W);
+        assert_eq!(event.value, 2);
+    }
+
+    #[test]
+    fn split_reads_are_completed() {
+        // Hands out three bytes at a time, like a stream interrupted mid-event.
+        struct Trickle<'a>(&'a [u8]);
+        impl Read for Trickle<'_> {
+            fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+                let n = out.len().min(3).min(self.0.len());
+                out[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
             }
         }
+        let mut buf = [0u8; INPUT_EVENT_SIZE * 2];
+        buf[INPUT_EVENT_SIZE + 18..INPUT_EVENT_SIZE + 20].copy_from_slice(&KEY_W.to_ne_bytes());
+
+        let mut stream = Trickle(&buf);
+        assert_eq!(InputEvent::read_from(&mut stream).unwrap().code, 0);
+        assert_eq!(InputEvent::read_from(&mut stream).unwrap().code, KEY_W);
+        assert!(InputEvent::read_from(&mut stream).is_err());
+    }
+
+    #[test]
+    fn paused_snake_outlasts_the_idle_timeout() {
+        let config = Arc::new(Config::default());
+        let timeout = Duration::from_secs(config.idle_timeouts["snake"]);
+        let mut state = AppState::new(config);
+        state.enter_mode(Mode::Snake);
+        state.handle_input(KEY_SPACE, KeyState::Pressed);
+        state.last_input = Instant::now() - timeout * 2;
+        state.update();
+        assert!(state.mode == Mode::Snake);
+
+        state.handle_input(KEY_SPACE, KeyState::Pressed);
+        state.last_input = Instant::now() - timeout * 2;
+        state.update();
+        assert!(state.mode != Mode::Snake);
+    }
+
+    #[test]
+    fn esc_leaves_modes_but_not_games() {
+        let mut state = AppState::new(Arc::new(Config::default()));
+        state.enter_mode(Mode::Rainbow);
+        state.handle_input(KEY_ESC, KeyState::Pressed);
+        assert!(state.mode == Mode::Ambient);
+
+        state.enter_mode(Mode::Snake);
+        state.handle_input(KEY_ESC, KeyState::Pressed);
+        assert!(state.mode == Mode::Snake);
+    }
+
+    #[test]
+    fn tetris_moves_are_not_cheats() {
+        let mut state = AppState::new(Arc::new(Config::default()));
+        state.enter_mode(Mode::Tetris);
+        // The metronome sequence, also a shuffle and two rotations.
+        for code in [KEY_LEFT, KEY_RIGHT, KEY_LEFT, KEY_RIGHT, KEY_UP, KEY_UP] {
+            state.handle_input(code, KeyState::Pressed);
+        }
+        assert!(state.mode == Mode::Tetris);
+    }
 
-        // 3. UPDATE MOUSE
-        for mouse in &devices.mice {
-            let base_color = state.get_water_base(10.0, 3.0);
-            let count = mouse.num_leds();
-            let leds = vec![base_color; count];
-            let _ = mouse.set_leds(leds).await;
+    #[test]
+    fn minesweeper_cursor_is_not_a_cheat() {
+        let mut state = AppState::new(Arc::new(Config::default()));
+        state.enter_mode(Mode::Minesweeper);
+        // The blend sequence, walking the cursor.
+        for code in [KEY_UP, KEY_UP, KEY_DOWN, KEY_DOWN, KEY_LEFT, KEY_LEFT] {
+            state.handle_input(code, KeyState::Pressed);
         }
+        assert!(state.mode == Mode::Minesweeper);
+    }
+
+    #[test]
+    fn gamepad_directions_become_arrows() {
+        let event = |type_, code, value| InputEvent {
+            time_sec: 0,
+            time_usec: 0,
+            type_,
+            code,
+            value,
+        };
+        let mut gamepad = Gamepad::new([(0, 255), (0, 255)]);
+        let mut out = Vec::new();
+        for e in [
+            event(EV_ABS, ABS_X, 250),   // stick right
+            event(EV_ABS, ABS_X, 240),   // still right
+            event(EV_ABS, ABS_X, 10),    // straight over to left
+            event(EV_ABS, ABS_HAT0Y, 1), // d-pad down
+            event(EV_ABS, ABS_HAT0Y, 0),
+        ] {
+            gamepad.translate(&e, &mut out);
+        }
+        let keys: Vec<(u16, KeyState)> = out
+            .iter()
+            .map(|input| match input {
+                Input::Key(code, state) => (*code, *state),
+                Input::Led(..) => panic!("gamepads have no LEDs"),
+            })
+            .collect();
+        use KeyState::{Pressed, Released};
+        assert_eq!(
+            keys,
+            [
+                (KEY_RIGHT, Pressed),
+                (KEY_RIGHT, Released),
+                (KEY_LEFT, Pressed),
+                (KEY_DOWN, Pressed),
+                (KEY_DOWN, Released),
+            ]
+        );
+    }
+
+    #[test]
+    fn short_read_is_an_error() {
+        let buf = [0u8; INPUT_EVENT_SIZE - 1];
+        assert!(InputEvent::read_from(&mut &buf[..]).is_err());
+    }
+
+    #[test]
+    fn night_fades_in_after_dusk_and_out_after_dawn() {
+        let day_night = config::DayNightConfig::default(); // 07:00 and 21:00, 30 min
+        let at = |h: u32, m: u32| day_night.night_mix(h * 3600 + m * 60);
+        assert_eq!(at(12, 0), 0.0);
+        assert_eq!(at(21, 15), 0.5);
+        assert_eq!(at(3, 0), 1.0);
+        assert_eq!(at(7, 15), 0.5);
+        assert_eq!(at(7, 30), 0.0);
+    }
+
+    #[test]
+    fn device_type_wins_over_name() {
+        assert_eq!(
+            categorize(DeviceType::DRam, "Corsair Vengeance Pro"),
+            Category::Ram
+        );
+        // "Gigabyte" used to be a RAM keyword.
+        assert_eq!(
+            categorize(DeviceType::Motherboard, "Gigabyte RGB Fusion"),
+            Category::Fan
+        );
+    }
+
+    #[test]
+    fn unknown_type_falls_back_to_name() {
+        assert_eq!(
+            categorize(DeviceType::Unknown, "Razer BlackWidow"),
+            Category::Keyboard
+        );
+        assert_eq!(categorize(DeviceType::Unknown, "Trident Z"), Category::Ram);
+    }
+
+    #[tokio::test]
+    async fn game_over_flashes_red_and_black() {
+        let mut config = Config {
+            transition_secs: 0.0,
+            fade_in_secs: 0.0,
+            ..Default::default()
+        };
+        config.bloom.enabled = false;
+        let config = Arc::new(config);
+        let mut state = AppState::new(config.clone());
+        state.enter_mode(Mode::GameOver);
+
+        let devices = Arc::new(mock_devices(&config));
+        let mut buffers = FrameBuffers::default();
+        let mut health = HashMap::new();
+        let mut writes = Writes::default();
+        // Just under the one second of flashing, at one frame per tick.
+        for tick in 0..33 {
+            fill_frame(
+                &mut state.snapshot(Vec::new()),
+                &devices,
+                &mut buffers,
+                tick,
+            );
+            send_frame(
+                &devices,
+                &mut buffers,
+                &mut health,
+                &mut writes,
+                &config,
+                &Metrics::default(),
+            )
+            .await;
+            state.update();
+        }
+
+        let (red, black) = (Color::new(255, 0, 0), Color::new(0, 0, 0));
+        let frames = devices.keyboards[0].frames();
+        assert_eq!(frames.len(), 4, "unchanged frames are not resent");
+        for (frame, expected) in frames.iter().zip([red, black, red, black]) {
+            assert!(frame.iter().all(|&c| c == expected));
+        }
+    }
+
+    // Takes 40ms over every frame.
+    struct SlowDevice(std::sync::atomic::AtomicUsize);
+
+    impl Device for SlowDevice {
+        fn id(&self) -> usize {
+            0
+        }
+
+        fn name(&self) -> &str {
+            "Slow"
+        }
+
+        fn num_leds(&self) -> usize {
+            1
+        }
+
+        async fn init(&self) -> OpenRgbResult<()> {
+            Ok(())
+        }
+
+        async fn set_leds(&self, _leds: &[Color]) -> OpenRgbResult<()> {
+            tokio::time::sleep(Duration::from_millis(40)).await;
+            self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn slow_writes_finish_and_their_device_is_skipped_meanwhile() {
+        let config = Config {
+            device_timeout_ms: 10,
+            ..Default::default()
+        };
+        let devices = Arc::new(DeviceGroup {
+            keyboards: vec![SlowDevice(Default::default())],
+            keyboard_layouts: vec![None],
+            ..Default::default()
+        });
+        let slow = &devices.keyboards[0];
+        let written = || slow.0.load(std::sync::atomic::Ordering::Relaxed);
+        let mut buffers = FrameBuffers::default();
+        let mut health = HashMap::new();
+        let mut writes = Writes::default();
+        let mut send = async |buffers: &mut FrameBuffers, shade| {
+            buffers.next(slow).push(Color::new(shade, 0, 0));
+            send_frame(
+                &devices,
+                buffers,
+                &mut health,
+                &mut writes,
+                &config,
+                &Metrics::default(),
+            )
+            .await
+        };
+
+        assert_eq!(send(&mut buffers, 1).await, (1, 0));
+        assert_eq!(send(&mut buffers, 2).await, (1, 0), "busy, skipped");
+        assert_eq!(written(), 0);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // The first write was left running, not dropped.
+        assert_eq!(send(&mut buffers, 3).await, (1, 1));
+        assert_eq!(written(), 1);
+    }
+
+    #[test]
+    fn maps_known_key_rows() {
+        let grid = |code| key_to_grid(code, GRID_WIDTH as i32, GRID_HEIGHT as i32);
+        // F1-F10 are codes 59-68, the number row 1-0 is 2-11.
+        for i in 0..10 {
+            assert_eq!(grid(59 + i), Some((2 + i as i32, 0)));
+            assert_eq!(grid(2 + i), Some((1 + i as i32, 1)));
+        }
+        // Q-P, A-L and Z-M.
+        for i in 0..10 {
+            assert_eq!(grid(16 + i), Some((1 + i as i32, 2)));
+        }
+        for i in 0..9 {
+            assert_eq!(grid(30 + i), Some((1 + i as i32, 3)));
+        }
+        for i in 0..7 {
+            assert_eq!(grid(44 + i), Some((1 + i as i32, 4)));
+        }
+        assert_eq!(grid(KEY_UP), Some((19, 4)));
+        assert_eq!(grid(KEY_LEFT), Some((18, 5)));
+        assert_eq!(grid(KEY_DOWN), Some((19, 5)));
+        assert_eq!(grid(KEY_RIGHT), Some((20, 5)));
+    }
+
+    #[test]
+    fn unmapped_or_off_grid_keys_are_none() {
+        assert_eq!(key_to_grid(0, 22, 6), None);
+        assert_eq!(key_to_grid(240, 22, 6), None);
+        // The arrows are off a grid shrunk to the main block.
+        assert_eq!(key_to_grid(KEY_RIGHT, 15, 6), None);
+        assert_eq!(key_to_grid(KEY_W, 15, 6), Some((2, 2)));
+    }
 
-        // 4. UPDATE FANS (Force Off)
-        for fan in &devices.fans {
-            let count = fan.num_leds();
-            let leds = vec![Color::new(0, 0, 0); count];
-            let _ = fan.set_leds(leds).await;
+    #[test]
+    fn key_cells_stay_on_a_small_grid() {
+        let config = Arc::new(Config {
+            grid_width: 4,
+            grid_height: 2,
+            ..Default::default()
+        });
+        let mut state = AppState::new(config);
+        for code in [0, KEY_RIGHT, 240] {
+            let (x, y) = state.key_cell(code);
+            assert!((0..4).contains(&x) && (0..2).contains(&y));
         }
     }
 }
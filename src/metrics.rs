@@ -0,0 +1,134 @@
+// rgb ctrl
+// Copyright (C) 2025 mari
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 2 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+// Render loop counters in the Prometheus text format, served on their own
+// address so they can stay private while the HTTP API is exposed:
+//
+//   $ curl localhost:9100/metrics
+//   rgbctrl_frames_rendered_total 5312
+//   ...
+
+use crate::{AppState, http};
+use log::{info, warn};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+
+#[derive(Default)]
+pub struct Metrics {
+    frames: AtomicU64,
+    frame_micros: AtomicU64,                    // total time spent rendering
+    device_errors: Mutex<HashMap<String, u64>>, // failed writes by device name
+}
+
+impl Metrics {
+    // One frame filled and sent, taking `elapsed`.
+    pub fn frame(&self, elapsed: Duration) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        self.frame_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn device_error(&self, name: &str) {
+        *self
+            .device_errors
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default() += 1;
+    }
+
+    fn render(&self, mode: &str) -> String {
+        let frames = self.frames.load(Ordering::Relaxed);
+        let secs = self.frame_micros.load(Ordering::Relaxed) as f64 / 1e6;
+        let average = if frames > 0 {
+            secs / frames as f64
+        } else {
+            0.0
+        };
+        let mut out = String::new();
+        let _ = writeln!(out, "# TYPE rgbctrl_frames_rendered_total counter");
+        let _ = writeln!(out, "rgbctrl_frames_rendered_total {}", frames);
+        let _ = writeln!(out, "# TYPE rgbctrl_frame_render_seconds summary");
+        let _ = writeln!(out, "rgbctrl_frame_render_seconds_sum {}", secs);
+        let _ = writeln!(out, "rgbctrl_frame_render_seconds_count {}", frames);
+        let _ = writeln!(out, "# TYPE rgbctrl_frame_render_seconds_average gauge");
+        let _ = writeln!(out, "rgbctrl_frame_render_seconds_average {}", average);
+        let _ = writeln!(out, "# TYPE rgbctrl_mode gauge");
+        let _ = writeln!(out, "rgbctrl_mode{{mode=\"{}\"}} 1", label(mode));
+        let _ = writeln!(out, "# TYPE rgbctrl_device_errors_total counter");
+        let errors = self.device_errors.lock().unwrap();
+        let mut names: Vec<_> = errors.keys().collect();
+        names.sort();
+        for name in names {
+            let _ = writeln!(
+                out,
+                "rgbctrl_device_errors_total{{device=\"{}\"}} {}",
+                label(name),
+                errors[name]
+            );
+        }
+        out
+    }
+}
+
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+async fn serve_client(
+    stream: TcpStream,
+    metrics: Arc<Metrics>,
+    state: Arc<Mutex<AppState>>,
+) -> std::io::Result<()> {
+    let (reader, writer) = stream.into_split();
+    let (status, body) = match http::read_request(reader).await? {
+        Some((method, path)) if path == "/metrics" => match method.as_str() {
+            "GET" => {
+                let mode = state.lock().unwrap().mode.name();
+                ("200 OK", metrics.render(mode))
+            }
+            _ => ("405 Method Not Allowed", "method not allowed".to_string()),
+        },
+        Some(_) => ("404 Not Found", "not found".to_string()),
+        None => ("400 Bad Request", "malformed request".to_string()),
+    };
+    http::respond(writer, status, "text/plain; version=0.0.4", &body).await
+}
+
+pub async fn serve(
+    addr: &str,
+    metrics: Arc<Metrics>,
+    state: Arc<Mutex<AppState>>,
+) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Metrics listening on {}", addr);
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let (metrics, state) = (metrics.clone(), state.clone());
+        tokio::spawn(async move {
+            if let Err(e) = serve_client(stream, metrics, state).await {
+                warn!("Metrics client error: {}", e);
+            }
+        });
+    }
+}
@@ -0,0 +1,238 @@
+// rgb ctrl
+// Copyright (C) 2025 mari
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 2 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+// Minesweeper on the whole grid. Mines are laid on the first reveal, clear
+// of the cursor and its neighbors, so the opening move is always safe.
+
+use crate::effect::Effect;
+use crate::{KEY_A, KEY_D, KEY_DOWN, KEY_F, KEY_LEFT, KEY_RIGHT, KEY_S, KEY_SPACE, KEY_UP, KEY_W};
+use crate::{Mode, scale_color};
+use openrgb2::Color;
+use rand::rngs::StdRng;
+use rand::seq::index;
+
+// One mine per this many cells.
+const CELLS_PER_MINE: usize = 8;
+
+// What a cell shows.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Cell {
+    Hidden,
+    Flagged,
+    Revealed(u8), // mines around it
+}
+
+pub struct Minesweeper {
+    w: i32,
+    h: i32,
+    mines: Vec<bool>, // row-major, empty until the first reveal
+    cells: Vec<Cell>,
+    pub cursor: (i32, i32),
+    rng: StdRng,
+    over: bool,   // a mine went off or every safe cell is open
+    elapsed: f32, // seconds, for the cursor blink
+}
+
+impl Minesweeper {
+    pub fn new(w: i32, h: i32, rng: StdRng) -> Self {
+        Minesweeper {
+            w,
+            h,
+            mines: Vec::new(),
+            cells: vec![Cell::Hidden; (w * h) as usize],
+            cursor: (w / 2, h / 2),
+            rng,
+            over: false,
+            elapsed: 0.0,
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        ((0..self.w).contains(&x) && (0..self.h).contains(&y)).then(|| (y * self.w + x) as usize)
+    }
+
+    fn neighbors(&self, x: i32, y: i32) -> impl Iterator<Item = (i32, i32)> + use<> {
+        (-1..=1)
+            .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+            .filter(|&d| d != (0, 0))
+            .map(move |(dx, dy)| (x + dx, y + dy))
+    }
+
+    fn lay_mines(&mut self) {
+        let (cx, cy) = self.cursor;
+        let safe = |i: usize| {
+            let (x, y) = (i as i32 % self.w, i as i32 / self.w);
+            (x - cx).abs() <= 1 && (y - cy).abs() <= 1
+        };
+        let open: Vec<usize> = (0..self.cells.len()).filter(|&i| !safe(i)).collect();
+        let count = (self.cells.len() / CELLS_PER_MINE).min(open.len());
+        self.mines = vec![false; self.cells.len()];
+        for i in index::sample(&mut self.rng, open.len(), count) {
+            self.mines[open[i]] = true;
+        }
+    }
+
+    fn mines_around(&self, x: i32, y: i32) -> u8 {
+        self.neighbors(x, y)
+            .filter_map(|(nx, ny)| self.index(nx, ny))
+            .filter(|&i| self.mines[i])
+            .count() as u8
+    }
+
+    pub fn move_cursor(&mut self, dx: i32, dy: i32) {
+        let (x, y) = self.cursor;
+        self.cursor = ((x + dx).clamp(0, self.w - 1), (y + dy).clamp(0, self.h - 1));
+    }
+
+    pub fn toggle_flag(&mut self) {
+        let Some(i) = self.index(self.cursor.0, self.cursor.1) else {
+            return;
+        };
+        self.cells[i] = match self.cells[i] {
+            Cell::Hidden => Cell::Flagged,
+            Cell::Flagged => Cell::Hidden,
+            revealed => revealed,
+        };
+    }
+
+    // Reveals the cell under the cursor, opening up every connected cell
+    // without mines around it. Returns false when it was a mine.
+    pub fn reveal(&mut self) -> bool {
+        if self.mines.is_empty() {
+            self.lay_mines();
+        }
+        let Some(i) = self.index(self.cursor.0, self.cursor.1) else {
+            return true;
+        };
+        if self.cells[i] != Cell::Hidden {
+            return true;
+        }
+        if self.mines[i] {
+            return false;
+        }
+        let mut open = vec![self.cursor];
+        while let Some((x, y)) = open.pop() {
+            let Some(i) = self.index(x, y) else {
+                continue;
+            };
+            if self.cells[i] != Cell::Hidden {
+                continue;
+            }
+            let around = self.mines_around(x, y);
+            self.cells[i] = Cell::Revealed(around);
+            if around == 0 {
+                open.extend(self.neighbors(x, y));
+            }
+        }
+        true
+    }
+
+    pub fn revealed(&self) -> usize {
+        self.cells
+            .iter()
+            .filter(|c| matches!(c, Cell::Revealed(_)))
+            .count()
+    }
+
+    // Every cell without a mine is open.
+    pub fn won(&self) -> bool {
+        !self.mines.is_empty()
+            && self
+                .cells
+                .iter()
+                .zip(&self.mines)
+                .all(|(c, &mine)| mine || matches!(c, Cell::Revealed(_)))
+    }
+
+    pub fn cell(&self, x: i32, y: i32) -> Option<Cell> {
+        self.index(x, y).map(|i| self.cells[i])
+    }
+}
+
+// Space digs, F plants or lifts a flag.
+impl Effect for Minesweeper {
+    fn update(&mut self, dt: f32) {
+        self.elapsed += dt;
+    }
+
+    // The cursor blinks; open cells glow brighter the more mines they touch.
+    fn color_at(&self, x: i32, y: i32) -> Color {
+        if (x, y) == self.cursor && ((self.elapsed * 4.0) as u32).is_multiple_of(2) {
+            return Color::new(255, 255, 255);
+        }
+        match self.cell(x, y) {
+            Some(Cell::Hidden) => Color::new(20, 20, 35),
+            Some(Cell::Flagged) => Color::new(255, 60, 0),
+            Some(Cell::Revealed(0)) => Color::new(0, 0, 0),
+            Some(Cell::Revealed(n)) => {
+                scale_color(Color::new(0, 255, 80), (n as f32 / 4.0).min(1.0))
+            }
+            None => Color::new(0, 0, 0),
+        }
+    }
+
+    fn key_down(&mut self, code: u16, _x: i32, _y: i32) {
+        match code {
+            KEY_LEFT | KEY_A => self.move_cursor(-1, 0),
+            KEY_RIGHT | KEY_D => self.move_cursor(1, 0),
+            KEY_UP | KEY_W => self.move_cursor(0, -1),
+            KEY_DOWN | KEY_S => self.move_cursor(0, 1),
+            KEY_F => self.toggle_flag(),
+            KEY_SPACE => {
+                let safe = self.reveal();
+                self.over |= !safe || self.won();
+            }
+            _ => {}
+        }
+    }
+
+    fn next_mode(&self) -> Option<Mode> {
+        self.over.then_some(Mode::GameOver)
+    }
+
+    fn score(&self) -> Option<usize> {
+        Some(self.revealed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn first_reveal_is_safe_and_opens_an_area() {
+        for seed in 0..50 {
+            let mut game = Minesweeper::new(22, 6, StdRng::seed_from_u64(seed));
+            assert!(game.reveal());
+            assert_eq!(game.cell(11, 3), Some(Cell::Revealed(0)));
+            assert!(game.revealed() >= 9);
+            assert_eq!(game.mines.iter().filter(|&&m| m).count(), 22 * 6 / 8);
+        }
+    }
+
+    #[test]
+    fn digging_up_a_mine_ends_the_game() {
+        let mut game = Minesweeper::new(22, 6, StdRng::seed_from_u64(7));
+        game.key_down(KEY_SPACE, 0, 0);
+        assert!(game.next_mode().is_none());
+
+        let mine = game.mines.iter().position(|&m| m).unwrap() as i32;
+        game.cursor = (mine % 22, mine / 22);
+        game.key_down(KEY_SPACE, 0, 0);
+        assert!(game.next_mode() == Some(Mode::GameOver));
+    }
+}
@@ -0,0 +1,155 @@
+// rgb ctrl
+// Copyright (C) 2025 mari
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 2 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+// Tints the ambient water after what an MPRIS player is playing. Needs the
+// `mpris` feature; without it, or with nothing playing, the palette is left
+// alone.
+
+#[cfg(feature = "mpris")]
+use crate::color::FromHsv;
+use openrgb2::Color;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+// The current track's tint, shared between the polling task and every copy
+// of the water. Stored as 0xRRGGBB; NONE means nothing is playing.
+#[derive(Clone)]
+pub struct NowPlaying(Arc<AtomicU32>);
+
+impl Default for NowPlaying {
+    fn default() -> Self {
+        NowPlaying(Arc::new(AtomicU32::new(NowPlaying::NONE)))
+    }
+}
+
+impl NowPlaying {
+    const NONE: u32 = u32::MAX;
+
+    pub fn get(&self) -> Option<Color> {
+        let v = self.0.load(Ordering::Relaxed);
+        (v != NowPlaying::NONE).then(|| Color::new((v >> 16) as u8, (v >> 8) as u8, v as u8))
+    }
+
+    pub fn set(&self, tint: Option<Color>) {
+        let v = tint.map_or(NowPlaying::NONE, |c| {
+            (c.r as u32) << 16 | (c.g as u32) << 8 | c.b as u32
+        });
+        self.0.store(v, Ordering::Relaxed);
+    }
+}
+
+// The average of the cover art when it is a local PNG, otherwise a hue
+// picked from `key` (artist and album), so a record always gets the same one.
+#[cfg(feature = "mpris")]
+fn track_color(art_url: Option<&str>, key: &str) -> Color {
+    if let Some(color) = art_url
+        .and_then(|url| url.strip_prefix("file://"))
+        .and_then(art_color)
+    {
+        return color;
+    }
+    let hash = key
+        .bytes()
+        .fold(0u32, |h, b| h.wrapping_mul(31).wrapping_add(b as u32));
+    Color::from_hsv((hash % 360) as f32, 0.7, 1.0)
+}
+
+#[cfg(feature = "mpris")]
+fn art_color(path: &str) -> Option<Color> {
+    let image = image::open(path).ok()?.into_rgb8();
+    let (mut sums, mut n) = ([0u64; 3], 0u64);
+    // Every few pixels is plenty for one average.
+    for px in image.pixels().step_by(7) {
+        for (sum, v) in sums.iter_mut().zip(px.0) {
+            *sum += v as u64;
+        }
+        n += 1;
+    }
+    let [r, g, b] = sums.map(|s| (s / n.max(1)) as u8);
+    (n > 0).then(|| Color::new(r, g, b))
+}
+
+// Looks for a playing player every `interval` until the process exits.
+#[cfg(feature = "mpris")]
+pub async fn poll(now_playing: NowPlaying, interval: Duration) {
+    let mut conn = None;
+    loop {
+        if conn.is_none() {
+            conn = zbus::Connection::session()
+                .await
+                .inspect_err(|e| log::debug!("No session bus: {}", e))
+                .ok();
+        }
+        let tint = match &conn {
+            Some(conn) => playing_track(conn).await.unwrap_or_else(|e| {
+                log::debug!("MPRIS query failed: {}", e);
+                None
+            }),
+            None => None,
+        };
+        now_playing.set(tint);
+        tokio::time::sleep(interval).await;
+    }
+}
+
+// The tint for the first player that reports Playing.
+#[cfg(feature = "mpris")]
+async fn playing_track(conn: &zbus::Connection) -> zbus::Result<Option<Color>> {
+    use std::collections::HashMap;
+    use zbus::zvariant::OwnedValue;
+
+    let names = zbus::fdo::DBusProxy::new(conn).await?.list_names().await?;
+    for name in names {
+        if !name.starts_with("org.mpris.MediaPlayer2.") {
+            continue;
+        }
+        let player = zbus::Proxy::new(
+            conn,
+            name.as_str(),
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.Player",
+        )
+        .await?;
+        let status: String = player.get_property("PlaybackStatus").await?;
+        if status != "Playing" {
+            continue;
+        }
+        let metadata: HashMap<String, OwnedValue> = player.get_property("Metadata").await?;
+        let text = |key: &str| {
+            metadata
+                .get(key)
+                .and_then(|v| String::try_from(v.try_clone().ok()?).ok())
+        };
+        let artists = metadata
+            .get("xesam:artist")
+            .and_then(|v| Vec::<String>::try_from(v.try_clone().ok()?).ok())
+            .unwrap_or_default();
+        let key = format!(
+            "{} {}",
+            artists.join(", "),
+            text("xesam:album").unwrap_or_default()
+        );
+        return Ok(Some(track_color(text("mpris:artUrl").as_deref(), &key)));
+    }
+    Ok(None)
+}
+
+#[cfg(not(feature = "mpris"))]
+pub async fn poll(now_playing: NowPlaying, _interval: Duration) {
+    log::warn!("Built without the mpris feature, keeping the static palette");
+    now_playing.set(None);
+}
@@ -0,0 +1,56 @@
+// rgb ctrl
+// Copyright (C) 2025 mari
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 2 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+// `--preview`: the keyboard grid drawn in the terminal with truecolor
+// escapes, two columns per cell so keys come out roughly square.
+
+use openrgb2::Color;
+use std::fmt::Write as _;
+use std::io::Write as _;
+
+pub struct Preview {
+    out: String, // reused across frames
+    first: bool,
+}
+
+impl Preview {
+    pub fn new() -> Self {
+        Preview {
+            out: String::new(),
+            first: true,
+        }
+    }
+
+    pub fn draw(&mut self, grid: &[Color], width: usize, brightness: f32) {
+        self.out.clear();
+        // Clear once, then redraw in place from the top-left corner.
+        if self.first {
+            self.out.push_str("\x1b[2J");
+            self.first = false;
+        }
+        self.out.push_str("\x1b[H");
+        for row in grid.chunks(width.max(1)) {
+            for &c in row {
+                let c = crate::scale_color(c, brightness);
+                let _ = write!(self.out, "\x1b[48;2;{};{};{}m  ", c.r, c.g, c.b);
+            }
+            self.out.push_str("\x1b[0m\n");
+        }
+        let mut stdout = std::io::stdout().lock();
+        let _ = stdout.write_all(self.out.as_bytes());
+        let _ = stdout.flush();
+    }
+}
@@ -0,0 +1,70 @@
+// rgb ctrl
+// Copyright (C) 2025 mari
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 2 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use openrgb2::Color;
+
+/// Averages the captured region into `columns` vertical slices, left to right.
+/// Returns `None` when capture is unavailable (feature off, no display, etc).
+#[cfg(feature = "screen")]
+pub fn capture_columns(region: Option<(u32, u32, u32, u32)>, columns: usize) -> Option<Vec<Color>> {
+    use xcap::Monitor;
+
+    let monitors = Monitor::all().ok()?;
+    let monitor = monitors
+        .iter()
+        .find(|m| m.is_primary().unwrap_or(false))
+        .or(monitors.first())?;
+    let image = match region {
+        Some((x, y, w, h)) => monitor.capture_region(x, y, w, h).ok()?,
+        None => monitor.capture_image().ok()?,
+    };
+
+    let (w, h) = image.dimensions();
+    if w == 0 || h == 0 || columns == 0 {
+        return None;
+    }
+
+    // Sampling every few pixels is plenty for a 22-column average.
+    const STRIDE: u32 = 8;
+    let mut sums = vec![[0u64; 4]; columns];
+    for y in (0..h).step_by(STRIDE as usize) {
+        for x in (0..w).step_by(STRIDE as usize) {
+            let px = image.get_pixel(x, y).0;
+            let col = (x as usize * columns / w as usize).min(columns - 1);
+            sums[col][0] += px[0] as u64;
+            sums[col][1] += px[1] as u64;
+            sums[col][2] += px[2] as u64;
+            sums[col][3] += 1;
+        }
+    }
+
+    Some(
+        sums.iter()
+            .map(|s| {
+                let n = s[3].max(1);
+                Color::new((s[0] / n) as u8, (s[1] / n) as u8, (s[2] / n) as u8)
+            })
+            .collect(),
+    )
+}
+
+#[cfg(not(feature = "screen"))]
+pub fn capture_columns(
+    _region: Option<(u32, u32, u32, u32)>,
+    _columns: usize,
+) -> Option<Vec<Color>> {
+    None
+}
@@ -0,0 +1,581 @@
+// rgb ctrl
+// Copyright (C) 2025 mari
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 2 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+// Snake on a board centered in the grid, steered with the arrows or WASD.
+// With `two_player`, WASD steers a green snake and the arrows an orange one,
+// and the round ends when either crashes.
+
+use crate::config::{Config, SnakeConfig};
+use crate::effect::{Effect, Water};
+use crate::{KEY_A, KEY_D, KEY_DOWN, KEY_LEFT, KEY_RIGHT, KEY_S, KEY_SPACE, KEY_UP, KEY_W, Mode};
+use log::info;
+use openrgb2::Color;
+use rand::Rng;
+use rand::rngs::StdRng;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnakeDifficulty {
+    Normal,
+    // Speed ramps geometrically and a second food sometimes appears.
+    Hard,
+}
+
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnakeWalls {
+    None,
+    Pillars, // one near each corner
+    Bars,    // two upright bars across the middle rows
+    Random,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FoodKind {
+    Normal,
+    Phase,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Food {
+    pos: Point,
+    kind: FoodKind,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+struct Player {
+    body: Vec<Point>, // head first
+    direction: Point,
+    head: Color,
+    tail: Color,
+}
+
+impl Player {
+    // Three long, heading along `direction` from `head`.
+    fn new(head: Point, direction: Point, colors: (Color, Color)) -> Self {
+        Player {
+            body: (0..3)
+                .map(|i| Point {
+                    x: head.x - direction.x * i,
+                    y: head.y,
+                })
+                .collect(),
+            direction,
+            head: colors.0,
+            tail: colors.1,
+        }
+    }
+
+    fn next_head(&self) -> Point {
+        let head = self.body[0];
+        Point {
+            x: head.x + self.direction.x,
+            y: head.y + self.direction.y,
+        }
+    }
+
+    // Turns unless that would reverse into its own neck.
+    fn turn(&mut self, code: u16) {
+        let d = self.direction;
+        let new_dir = match code {
+            KEY_UP | KEY_W if d.y != 1 => Some(Point { x: 0, y: -1 }),
+            KEY_DOWN | KEY_S if d.y != -1 => Some(Point { x: 0, y: 1 }),
+            KEY_LEFT | KEY_A if d.x != 1 => Some(Point { x: -1, y: 0 }),
+            KEY_RIGHT | KEY_D if d.x != -1 => Some(Point { x: 1, y: 0 }),
+            _ => None,
+        };
+        if let Some(d) = new_dir {
+            self.direction = d;
+        }
+    }
+}
+
+const GREEN: (Color, Color) = (Color::new(0, 255, 0), Color::new(0, 150, 0));
+const ORANGE: (Color, Color) = (Color::new(255, 140, 0), Color::new(150, 70, 0));
+
+#[derive(Clone, Copy)]
+struct Board {
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+}
+
+// The smallest board `validate` accepts: room for a three-long snake with a
+// cell to turn into, and a row each for two players.
+pub const MIN_BOARD: (i32, i32) = (4, 2);
+
+// The board's width and height on a `grid_w` x `grid_h` grid.
+pub fn board_size(grid_w: i32, grid_h: i32, cfg: &SnakeConfig) -> (i32, i32) {
+    let (w, h) = cfg.board_size.unwrap_or_else(|| {
+        let w = ((grid_h as f32 * cfg.max_aspect).round() as i32).max(1);
+        (w, grid_h)
+    });
+    (w.clamp(1, grid_w), h.clamp(1, grid_h))
+}
+
+impl Board {
+    fn centered(grid_w: i32, grid_h: i32, cfg: &SnakeConfig) -> Self {
+        let (w, h) = board_size(grid_w, grid_h, cfg);
+        Board {
+            x: (grid_w - w) / 2,
+            y: (grid_h - h) / 2,
+            w,
+            h,
+        }
+    }
+
+    fn contains(&self, p: Point) -> bool {
+        p.x >= self.x && p.x < self.x + self.w && p.y >= self.y && p.y < self.y + self.h
+    }
+
+    // The obstacle cells for `layout`. Rows in `clear` (where the snakes
+    // start) are left open.
+    fn walls(
+        &self,
+        layout: SnakeWalls,
+        chance: f64,
+        clear: &[i32],
+        rng: &mut StdRng,
+    ) -> Vec<Point> {
+        let (x, y, w, h) = (self.x, self.y, self.w, self.h);
+        let cells: Vec<Point> = match layout {
+            SnakeWalls::None => Vec::new(),
+            SnakeWalls::Pillars => [(w / 4, h / 4), (w - 1 - w / 4, h / 4)]
+                .into_iter()
+                .flat_map(|(px, py)| [(px, py), (px, h - 1 - py)])
+                .map(|(px, py)| Point {
+                    x: x + px,
+                    y: y + py,
+                })
+                .collect(),
+            SnakeWalls::Bars => (h / 4..h - h / 4)
+                .flat_map(|py| {
+                    [w / 3, w - 1 - w / 3].map(|px| Point {
+                        x: x + px,
+                        y: y + py,
+                    })
+                })
+                .collect(),
+            SnakeWalls::Random => (y..y + h)
+                .flat_map(|py| (x..x + w).map(move |px| Point { x: px, y: py }))
+                .filter(|_| rng.random_bool(chance))
+                .collect(),
+        };
+        let mut walls: Vec<Point> = Vec::new();
+        for p in cells {
+            if !clear.contains(&p.y) && !walls.contains(&p) {
+                walls.push(p);
+            }
+        }
+        walls
+    }
+}
+
+pub struct Snake {
+    config: Arc<Config>,
+    water: Water, // shown around the board with `outside_ambient`
+    board: Board,
+    players: Vec<Player>, // one, or two with `two_player`
+    walls: Vec<Point>,
+    foods: Vec<Food>,
+    phasing_until: Option<Instant>,
+    step_ms: u64,
+    last_step: Instant,
+    paused_at: Option<Instant>,
+    dead: bool,
+    winner: Option<usize>, // the player left standing in a two-player round
+    rng: StdRng,
+}
+
+impl Snake {
+    pub fn new(config: Arc<Config>, water: Water, mut rng: StdRng) -> Self {
+        let board = Board::centered(
+            config.grid_width as i32,
+            config.grid_height as i32,
+            &config.snake,
+        );
+        let hx = board.x + (board.w / 2).min(2);
+        let (right, left) = (Point { x: 1, y: 0 }, Point { x: -1, y: 0 });
+        let players = if config.snake.two_player {
+            // Facing each other on the upper and lower thirds.
+            let (y1, y2) = (board.y + board.h / 3, board.y + board.h * 2 / 3);
+            let hx2 = board.x + board.w - 1 - (board.w / 2).min(2);
+            vec![
+                Player::new(Point { x: hx, y: y1 }, right, GREEN),
+                Player::new(Point { x: hx2, y: y2 }, left, ORANGE),
+            ]
+        } else {
+            vec![Player::new(
+                Point {
+                    x: hx,
+                    y: board.y + board.h / 2,
+                },
+                right,
+                GREEN,
+            )]
+        };
+        let rows: Vec<i32> = players.iter().map(|s| s.body[0].y).collect();
+        let cfg = &config.snake;
+        let walls = board.walls(cfg.walls, cfg.wall_chance, &rows, &mut rng);
+        let mut snake = Snake {
+            config,
+            water,
+            board,
+            players,
+            walls,
+            foods: Vec::new(),
+            phasing_until: None,
+            step_ms: 150,
+            last_step: Instant::now(),
+            paused_at: None,
+            dead: false,
+            winner: None,
+            rng,
+        };
+        snake.spawn_food();
+        snake
+    }
+
+    // Places a food on a free cell. False when there is none left.
+    fn spawn_food(&mut self) -> bool {
+        let b = self.board;
+        let free: Vec<Point> = (b.y..b.y + b.h)
+            .flat_map(|y| (b.x..b.x + b.w).map(move |x| Point { x, y }))
+            .filter(|&p| {
+                !self.occupied(p)
+                    && !self.walls.contains(&p)
+                    && !self.foods.iter().any(|f| f.pos == p)
+            })
+            .collect();
+        if free.is_empty() {
+            return false;
+        }
+        let pos = free[self.rng.random_range(0..free.len())];
+        let kind = if self.rng.random_bool(self.config.snake.powerup_chance) {
+            FoodKind::Phase
+        } else {
+            FoodKind::Normal
+        };
+        self.foods.push(Food { pos, kind });
+        true
+    }
+
+    fn occupied(&self, p: Point) -> bool {
+        self.players.iter().any(|s| s.body.contains(&p))
+    }
+
+    // Resuming shifts the step timer and any power-up by the time spent
+    // paused, so play continues exactly where it stopped.
+    fn toggle_pause(&mut self) {
+        match self.paused_at.take() {
+            None => self.paused_at = Some(Instant::now()),
+            Some(at) => {
+                let paused = at.elapsed();
+                self.last_step += paused;
+                if let Some(until) = &mut self.phasing_until {
+                    *until += paused;
+                }
+            }
+        }
+    }
+
+    fn step(&mut self) {
+        let b = self.board;
+        let heads: Vec<Point> = self
+            .players
+            .iter()
+            .map(|s| {
+                let mut head = s.next_head();
+                if self.config.snake.wrap {
+                    head.x = b.x + (head.x - b.x).rem_euclid(b.w);
+                    head.y = b.y + (head.y - b.y).rem_euclid(b.h);
+                }
+                head
+            })
+            .collect();
+
+        // The edges and walls, any body, or the other head arriving on the same cell.
+        let phasing = self.phasing_until.is_some_and(|t| Instant::now() < t);
+        let crashed: Vec<bool> = heads
+            .iter()
+            .enumerate()
+            .map(|(i, &head)| {
+                !self.board.contains(head)
+                    || self.walls.contains(&head)
+                    || (!phasing && self.occupied(head))
+                    || heads.iter().enumerate().any(|(j, &h)| j != i && h == head)
+            })
+            .collect();
+        if crashed.contains(&true) {
+            self.dead = true;
+            if self.players.len() > 1 {
+                self.winner = crashed.iter().position(|&c| !c);
+            }
+            return;
+        }
+
+        for (i, &new_head) in heads.iter().enumerate() {
+            self.eat(i, new_head);
+        }
+    }
+
+    // Moves player `i` onto `new_head`, growing if there was food there.
+    fn eat(&mut self, i: usize, new_head: Point) {
+        self.players[i].body.insert(0, new_head);
+        if let Some(idx) = self.foods.iter().position(|f| f.pos == new_head) {
+            let food = self.foods.remove(idx);
+            if food.kind == FoodKind::Phase {
+                self.phasing_until =
+                    Some(Instant::now() + Duration::from_secs(self.config.snake.phase_secs));
+            }
+            // Nowhere left to put food: the board is full and the round won.
+            if !self.spawn_food() && self.foods.is_empty() {
+                info!("Snake filled the board");
+                self.dead = true;
+                return;
+            }
+            match self.config.snake.difficulty {
+                SnakeDifficulty::Normal => {
+                    if self.step_ms > 50 {
+                        self.step_ms -= 2;
+                    }
+                }
+                SnakeDifficulty::Hard => {
+                    self.step_ms = (self.step_ms * 9 / 10).max(40);
+                    if self.foods.len() < 2 && self.rng.random_bool(0.3) {
+                        self.spawn_food();
+                    }
+                }
+            }
+        } else {
+            self.players[i].body.pop();
+        }
+    }
+}
+
+impl Effect for Snake {
+    fn update(&mut self, dt: f32) {
+        if self.config.snake.outside_ambient {
+            self.water.advance(dt / self.config.tick_secs());
+        }
+        if !self.dead
+            && self.paused_at.is_none()
+            && self.last_step.elapsed() >= Duration::from_millis(self.step_ms)
+        {
+            self.step();
+            self.last_step = Instant::now();
+        }
+    }
+
+    fn color_at(&self, x: i32, y: i32) -> Color {
+        let p = Point { x, y };
+        if !self.board.contains(p) {
+            if self.config.snake.outside_ambient {
+                return self.water.color(x as f32, y as f32);
+            }
+            return Color::new(0, 0, 0);
+        }
+        if let Some(player) = self.players.iter().find(|s| s.body.contains(&p)) {
+            if let Some(until) = self.phasing_until {
+                let left_ms = until.saturating_duration_since(Instant::now()).as_millis();
+                if left_ms > 0 && !(left_ms / 150).is_multiple_of(2) {
+                    return Color::new(0, 80, 160);
+                }
+            }
+            if player.body[0] == p {
+                if let Some(at) = self.paused_at
+                    && (at.elapsed().as_millis() / 400) % 2 == 1
+                {
+                    return Color::new(255, 255, 255);
+                }
+                return player.head;
+            }
+            return player.tail;
+        }
+        if self.walls.contains(&p) {
+            return Color::new(40, 40, 55);
+        }
+        if let Some(food) = self.foods.iter().find(|f| f.pos == p) {
+            return match food.kind {
+                FoodKind::Normal => Color::new(255, 0, 255),
+                FoodKind::Phase => Color::new(0, 255, 255),
+            };
+        }
+        Color::new(5, 5, 5)
+    }
+
+    fn key_down(&mut self, code: u16, _x: i32, _y: i32) {
+        if code == KEY_SPACE {
+            self.toggle_pause();
+            return;
+        }
+        if self.paused_at.is_some() {
+            return;
+        }
+        let arrows = matches!(code, KEY_UP | KEY_DOWN | KEY_LEFT | KEY_RIGHT);
+        let player = if arrows { self.players.len() - 1 } else { 0 };
+        self.players[player].turn(code);
+    }
+
+    fn next_mode(&self) -> Option<Mode> {
+        self.dead.then_some(Mode::GameOver)
+    }
+
+    // The winner's length in a two-player round, otherwise the longest.
+    fn score(&self) -> Option<usize> {
+        let longest = self.players.iter().map(|s| s.body.len()).max();
+        let len = self
+            .winner
+            .map(|i| self.players[i].body.len())
+            .or(longest)?;
+        Some(len.saturating_sub(3))
+    }
+
+    fn paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    fn exits_on_esc(&self) -> bool {
+        false
+    }
+
+    fn winner(&self) -> Option<Color> {
+        self.winner.map(|i| self.players[i].head)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sysmon::SystemLoad;
+    use rand::SeedableRng;
+
+    fn seeded(seed: u64) -> Snake {
+        let config = Arc::new(Config::default());
+        let water = Water::new(SystemLoad::default(), &config);
+        Snake::new(config, water, StdRng::seed_from_u64(seed))
+    }
+
+    fn food_at(snake: &Snake) -> Vec<(i32, i32)> {
+        snake.foods.iter().map(|f| (f.pos.x, f.pos.y)).collect()
+    }
+
+    #[test]
+    fn same_seed_places_the_same_food() {
+        let (mut a, mut b) = (seeded(7), seeded(7));
+        for _ in 0..5 {
+            a.spawn_food();
+            b.spawn_food();
+        }
+        assert_eq!(food_at(&a), food_at(&b));
+        assert_eq!(food_at(&a).len(), 6);
+    }
+
+    #[test]
+    fn eating_replaces_the_food_deterministically() {
+        let (mut a, mut b) = (seeded(42), seeded(42));
+        for snake in [&mut a, &mut b] {
+            let head = snake.players[0].body[0];
+            snake.foods = vec![Food {
+                pos: Point {
+                    x: head.x + 1,
+                    y: head.y,
+                },
+                kind: FoodKind::Normal,
+            }];
+            snake.step();
+            assert_eq!(snake.players[0].body.len(), 4);
+            assert!(!snake.dead);
+        }
+        assert_eq!(food_at(&a), food_at(&b));
+        assert!(a.foods.iter().all(|f| !a.occupied(f.pos)));
+    }
+
+    #[test]
+    fn filling_the_board_ends_the_round() {
+        let mut config = Config::default();
+        config.snake.board_size = Some((4, 2));
+        let config = Arc::new(config);
+        let water = Water::new(SystemLoad::default(), &config);
+        let mut snake = Snake::new(config, water, StdRng::seed_from_u64(5));
+        let (x, y) = (snake.board.x, snake.board.y);
+        let p = |dx, dy| Point {
+            x: x + dx,
+            y: y + dy,
+        };
+        // Everything but the last cell, which holds the food.
+        snake.players[0].body = vec![
+            p(2, 0),
+            p(1, 0),
+            p(0, 0),
+            p(0, 1),
+            p(1, 1),
+            p(2, 1),
+            p(3, 1),
+        ];
+        snake.foods = vec![Food {
+            pos: p(3, 0),
+            kind: FoodKind::Normal,
+        }];
+        snake.step();
+        assert!(snake.dead);
+        assert!(!snake.spawn_food());
+    }
+
+    #[test]
+    fn food_stays_off_random_walls() {
+        let mut config = Config::default();
+        config.snake.walls = SnakeWalls::Random;
+        config.snake.wall_chance = 0.4;
+        let config = Arc::new(config);
+        let water = Water::new(SystemLoad::default(), &config);
+        let mut snake = Snake::new(config, water, StdRng::seed_from_u64(3));
+        for _ in 0..10 {
+            snake.spawn_food();
+        }
+        assert!(!snake.walls.is_empty());
+        let start_row = snake.players[0].body[0].y;
+        assert!(snake.walls.iter().all(|w| w.y != start_row));
+        assert!(snake.foods.iter().all(|f| !snake.walls.contains(&f.pos)));
+    }
+
+    #[test]
+    fn crashing_hands_the_round_to_the_other_player() {
+        let mut config = Config::default();
+        config.snake.two_player = true;
+        let config = Arc::new(config);
+        let water = Water::new(SystemLoad::default(), &config);
+        let mut snake = Snake::new(config, water, StdRng::seed_from_u64(1));
+        snake.foods.clear();
+
+        // Green steers up until it leaves the board.
+        snake.key_down(KEY_W, 0, 0);
+        for _ in 0..snake.board.h {
+            snake.step();
+        }
+        assert!(snake.dead);
+        assert!(snake.winner() == Some(ORANGE.0));
+    }
+}
@@ -0,0 +1,82 @@
+// rgb ctrl
+// Copyright (C) 2025 mari
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 2 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+// PNG dumps of the keyboard grid, through the same output stage (bloom,
+// brightness, gamma) as the frames sent to the hardware.
+
+use crate::{AppState, GammaLut, apply_bloom, config, scale_color};
+use image::{ImageFormat, RgbImage};
+use openrgb2::Color;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Pixels per grid cell, so the picture is big enough to make out.
+const SCALE: u32 = 16;
+
+// `<state dir>/snapshots/snapshot-<unix time>.png`
+pub fn default_path() -> Option<PathBuf> {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    Some(
+        config::state_dir()?
+            .join("snapshots")
+            .join(format!("snapshot-{}.png", secs)),
+    )
+}
+
+// The grid as drawn, taken under the state lock; encoding waits for `save`.
+pub struct Snapshot {
+    grid: Vec<Color>,
+    width: u32,
+    height: u32,
+    brightness: f32,
+    gamma: f32,
+}
+
+pub fn capture(state: &AppState) -> Snapshot {
+    let cfg = &state.config;
+    let mut grid = Vec::new();
+    state.render_keyboard_frame(&mut grid);
+    if cfg.bloom.enabled {
+        apply_bloom(&mut grid, cfg.grid_width, cfg.grid_height, &cfg.bloom);
+    }
+    Snapshot {
+        grid,
+        width: cfg.grid_width as u32,
+        height: cfg.grid_height as u32,
+        brightness: state.brightness * state.idle_dim * state.fade_in,
+        gamma: cfg.gamma,
+    }
+}
+
+impl Snapshot {
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let gamma = GammaLut::new(self.gamma);
+        let w = self.width;
+        let image = RgbImage::from_fn(w * SCALE, self.height * SCALE, |px, py| {
+            let cell = self.grid[((py / SCALE) * w + px / SCALE) as usize];
+            let c = gamma.apply(scale_color(cell, self.brightness));
+            image::Rgb([c.r, c.g, c.b])
+        });
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        image
+            .save_with_format(path, ImageFormat::Png)
+            .map_err(|e| e.to_string())
+    }
+}
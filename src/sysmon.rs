@@ -0,0 +1,83 @@
+// rgb ctrl
+// Copyright (C) 2025 mari
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 2 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+// System load readings normalized to 0.0..=1.0 for tinting the ambient water.
+
+use crate::config::SystemTintConfig;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+// The latest reading, shared between the sampling task and every copy of the
+// water. Stored as f32 bits; NaN means no reading.
+#[derive(Clone)]
+pub struct SystemLoad(Arc<AtomicU32>);
+
+impl Default for SystemLoad {
+    fn default() -> Self {
+        SystemLoad(Arc::new(AtomicU32::new(f32::NAN.to_bits())))
+    }
+}
+
+impl SystemLoad {
+    pub fn get(&self) -> Option<f32> {
+        let v = f32::from_bits(self.0.load(Ordering::Relaxed));
+        (!v.is_nan()).then_some(v)
+    }
+
+    pub fn set(&self, load: Option<f32>) {
+        let v = load.unwrap_or(f32::NAN);
+        self.0.store(v.to_bits(), Ordering::Relaxed);
+    }
+}
+
+// CPU busy fraction since the previous sample, from the aggregate "cpu" line
+// of /proc/stat. The first call only primes the counters.
+#[derive(Default)]
+pub struct CpuSampler {
+    prev: Option<(u64, u64)>, // (busy, total) jiffies
+}
+
+impl CpuSampler {
+    pub fn sample(&mut self) -> Option<f32> {
+        let stat = std::fs::read_to_string("/proc/stat").ok()?;
+        let fields: Vec<u64> = stat
+            .lines()
+            .next()?
+            .strip_prefix("cpu ")?
+            .split_whitespace()
+            .filter_map(|v| v.parse().ok())
+            .collect();
+        // user nice system idle iowait irq softirq steal ...
+        let idle = fields.get(3)? + fields.get(4).unwrap_or(&0);
+        let total: u64 = fields.iter().take(8).sum();
+        let busy = total - idle;
+
+        let prev = self.prev.replace((busy, total));
+        let (prev_busy, prev_total) = prev?;
+        let dt = total.saturating_sub(prev_total);
+        if dt == 0 {
+            return None;
+        }
+        Some(busy.saturating_sub(prev_busy) as f32 / dt as f32)
+    }
+}
+
+// A hwmon `temp*_input` file (millidegrees C) mapped onto the configured range.
+pub fn read_temperature(cfg: &SystemTintConfig, path: &str) -> Option<f32> {
+    let milli: f32 = std::fs::read_to_string(path).ok()?.trim().parse().ok()?;
+    let span = (cfg.temp_max - cfg.temp_min).max(1.0);
+    Some(((milli / 1000.0 - cfg.temp_min) / span).clamp(0.0, 1.0))
+}
@@ -0,0 +1,274 @@
+// rgb ctrl
+// Copyright (C) 2025 mari
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 2 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+// Falling-block game in a well as tall as the grid. Pieces spawn above the
+// top row, so a piece that locks with any block still up there ends the game.
+
+use crate::effect::Effect;
+use crate::{KEY_A, KEY_D, KEY_DOWN, KEY_LEFT, KEY_RIGHT, KEY_S, KEY_UP, KEY_W, Mode};
+use openrgb2::Color;
+use rand::Rng;
+use rand::rngs::StdRng;
+
+const WELL_WIDTH: i32 = 10;
+
+// Blocks of each piece in spawn orientation, rotated about (1, 1).
+const SHAPES: [[(i32, i32); 4]; 7] = [
+    [(0, 1), (1, 1), (2, 1), (3, 1)], // I
+    [(1, 0), (2, 0), (1, 1), (2, 1)], // O
+    [(0, 1), (1, 1), (2, 1), (1, 0)], // T
+    [(1, 0), (2, 0), (0, 1), (1, 1)], // S
+    [(0, 0), (1, 0), (1, 1), (2, 1)], // Z
+    [(0, 0), (0, 1), (1, 1), (2, 1)], // J
+    [(2, 0), (0, 1), (1, 1), (2, 1)], // L
+];
+
+const COLORS: [Color; 7] = [
+    Color::new(0, 255, 255),
+    Color::new(255, 255, 0),
+    Color::new(160, 0, 255),
+    Color::new(0, 255, 0),
+    Color::new(255, 0, 0),
+    Color::new(0, 0, 255),
+    Color::new(255, 120, 0),
+];
+
+#[derive(Clone, Copy)]
+struct Piece {
+    kind: usize,
+    rot: usize,
+    x: i32,
+    y: i32,
+}
+
+impl Piece {
+    fn blocks(&self) -> impl Iterator<Item = (i32, i32)> {
+        let (rot, px, py) = (self.rot, self.x, self.y);
+        SHAPES[self.kind].into_iter().map(move |(mut bx, mut by)| {
+            for _ in 0..rot {
+                (bx, by) = (2 - by, bx);
+            }
+            (px + bx, py + by)
+        })
+    }
+}
+
+pub struct Tetris {
+    left: i32, // grid column of the well's left edge
+    w: i32,
+    h: i32,
+    locked: Vec<Option<usize>>, // piece kind per well cell, row-major
+    piece: Piece,
+    pub lines: u32,
+    rng: StdRng,
+    since_drop: f32, // seconds
+    over: bool,
+}
+
+impl Tetris {
+    pub fn new(grid_w: i32, grid_h: i32, rng: StdRng) -> Self {
+        let w = WELL_WIDTH.min(grid_w);
+        let mut t = Tetris {
+            left: (grid_w - w) / 2,
+            w,
+            h: grid_h,
+            locked: vec![None; (w * grid_h) as usize],
+            piece: Piece {
+                kind: 0,
+                rot: 0,
+                x: 0,
+                y: 0,
+            },
+            lines: 0,
+            rng,
+            since_drop: 0.0,
+            over: false,
+        };
+        t.spawn();
+        t
+    }
+
+    fn spawn(&mut self) {
+        self.piece = Piece {
+            kind: self.rng.random_range(0..SHAPES.len()),
+            rot: 0,
+            x: self.w / 2 - 2,
+            y: -2,
+        };
+    }
+
+    // Blocks above the well are allowed; walls, floor and the stack are not.
+    fn fits(&self, piece: &Piece) -> bool {
+        piece.blocks().all(|(x, y)| {
+            x >= 0
+                && x < self.w
+                && y < self.h
+                && (y < 0 || self.locked[(y * self.w + x) as usize].is_none())
+        })
+    }
+
+    fn try_move(&mut self, dx: i32, dy: i32, rot: usize) -> bool {
+        let moved = Piece {
+            x: self.piece.x + dx,
+            y: self.piece.y + dy,
+            rot: (self.piece.rot + rot) % 4,
+            ..self.piece
+        };
+        if self.fits(&moved) {
+            self.piece = moved;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn shift(&mut self, dx: i32) {
+        self.try_move(dx, 0, 0);
+    }
+
+    // Tries a plain rotation, then nudging one column either way off a wall.
+    pub fn rotate(&mut self) {
+        let _ = self.try_move(0, 0, 1) || self.try_move(-1, 0, 1) || self.try_move(1, 0, 1);
+    }
+
+    // Moves the piece down one row, locking it when it can't. Returns false
+    // once the stack has reached above the top row.
+    pub fn step(&mut self) -> bool {
+        if self.try_move(0, 1, 0) {
+            return true;
+        }
+        let mut topped_out = false;
+        for (x, y) in self.piece.blocks() {
+            if y < 0 {
+                topped_out = true;
+            } else {
+                self.locked[(y * self.w + x) as usize] = Some(self.piece.kind);
+            }
+        }
+        if topped_out {
+            return false;
+        }
+        self.clear_lines();
+        self.spawn();
+        self.fits(&self.piece)
+    }
+
+    fn clear_lines(&mut self) {
+        let w = self.w as usize;
+        let mut y = self.h as usize;
+        while y > 0 {
+            let row = (y - 1) * w;
+            if self.locked[row..row + w].iter().all(|c| c.is_some()) {
+                // Shift everything above down by one and re-check this row.
+                self.locked.copy_within(0..row, w);
+                self.locked[..w].fill(None);
+                self.lines += 1;
+            } else {
+                y -= 1;
+            }
+        }
+    }
+
+    // None outside the well; Some(None) for an empty well cell.
+    pub fn cell(&self, x: i32, y: i32) -> Option<Option<Color>> {
+        let x = x - self.left;
+        if x < 0 || x >= self.w || y < 0 || y >= self.h {
+            return None;
+        }
+        if self.piece.blocks().any(|b| b == (x, y)) {
+            return Some(Some(COLORS[self.piece.kind]));
+        }
+        Some(self.locked[(y * self.w + x) as usize].map(|k| COLORS[k]))
+    }
+
+    // Milliseconds between automatic drops; speeds up as lines clear.
+    pub fn drop_ms(&self) -> u64 {
+        500u64.saturating_sub(self.lines as u64 * 20).max(120)
+    }
+}
+
+// Left and right shift the piece, up rotates it and down drops it a row.
+impl Effect for Tetris {
+    fn update(&mut self, dt: f32) {
+        self.since_drop += dt;
+        if !self.over && self.since_drop * 1000.0 >= self.drop_ms() as f32 {
+            self.over = !self.step();
+            self.since_drop = 0.0;
+        }
+    }
+
+    fn color_at(&self, x: i32, y: i32) -> Color {
+        match self.cell(x, y) {
+            Some(Some(block)) => block,
+            Some(None) => Color::new(5, 5, 5),
+            None => Color::new(0, 0, 0),
+        }
+    }
+
+    fn key_down(&mut self, code: u16, _x: i32, _y: i32) {
+        if self.over {
+            return;
+        }
+        match code {
+            KEY_LEFT | KEY_A => self.shift(-1),
+            KEY_RIGHT | KEY_D => self.shift(1),
+            KEY_UP | KEY_W => self.rotate(),
+            KEY_DOWN | KEY_S => {
+                self.over = !self.step();
+                self.since_drop = 0.0;
+            }
+            _ => {}
+        }
+    }
+
+    fn next_mode(&self) -> Option<Mode> {
+        self.over.then_some(Mode::GameOver)
+    }
+
+    fn score(&self) -> Option<usize> {
+        Some(self.lines as usize)
+    }
+
+    fn exits_on_esc(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn full_row_clears_and_drops_the_stack() {
+        let mut game = Tetris::new(22, 6, StdRng::seed_from_u64(7));
+        let (w, h) = (game.w as usize, game.h as usize);
+        // A full bottom row with one block sitting on it, clear of the
+        // falling piece.
+        game.locked[(h - 1) * w..].fill(Some(0));
+        game.locked[(h - 2) * w] = Some(1);
+
+        for _ in 0..h {
+            if game.lines > 0 {
+                break;
+            }
+            assert!(game.step());
+        }
+        assert_eq!(game.lines, 1);
+        assert_eq!(game.locked[(h - 1) * w], Some(1));
+        assert_eq!(game.locked[(h - 2) * w], None);
+    }
+}